@@ -6,6 +6,7 @@ CREATE TABLE IF NOT EXISTS users (
     class smallint NOT NULL,
     CHECK (class >= 0 AND class <=3),
     is_admin boolean NOT NULL,
+    -- no longer read or written, skills are derived from completed_tasks
     tags text[] DEFAULT '{}'
 );
 
@@ -57,3 +58,420 @@ CREATE TABLE IF NOT EXISTS invite_tokens (
 );
 
 CREATE INDEX IF NOT EXISTS token_idx ON invite_tokens USING HASH (token);
+
+
+-- leaderboards
+
+CREATE OR REPLACE FUNCTION quest_xp(complexity smallint, expected_time real) RETURNS integer AS $$
+  SELECT CAST(ROUND(10 * (complexity + 1) * expected_time) AS integer)
+$$ LANGUAGE SQL IMMUTABLE;
+
+-- completions from before the column have no known time and stay NULL, only new rows get now()
+ALTER TABLE completed_tasks ADD COLUMN IF NOT EXISTS completed_at timestamptz;
+ALTER TABLE completed_tasks ALTER COLUMN completed_at DROP NOT NULL,
+  ALTER COLUMN completed_at SET DEFAULT now();
+ALTER TABLE completed_tasks ADD COLUMN IF NOT EXISTS xp integer;
+
+UPDATE completed_tasks SET xp = quest_xp(tasks.complexity, tasks.expected_time)
+  FROM tasks WHERE tasks.id = completed_tasks.task_id AND completed_tasks.xp IS NULL;
+
+CREATE INDEX IF NOT EXISTS completed_at_idx ON completed_tasks(completed_at);
+CREATE INDEX IF NOT EXISTS user_completed_at_idx ON completed_tasks(user_id, completed_at);
+CREATE INDEX IF NOT EXISTS tags_idx ON tasks USING GIN (tags);
+CREATE INDEX IF NOT EXISTS class_idx ON users(class);
+
+
+-- time tracking
+
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS assigned_at timestamptz DEFAULT NULL;
+
+CREATE TABLE IF NOT EXISTS worklogs (
+  id SERIAL PRIMARY KEY,
+  task_id INT NOT NULL,
+  CONSTRAINT fk_tasks
+    FOREIGN KEY(task_id)
+	    REFERENCES tasks(id)
+	    ON DELETE CASCADE,
+  user_id INT NOT NULL,
+  CONSTRAINT fk_users
+    FOREIGN KEY(user_id)
+	    REFERENCES users(id)
+	    ON DELETE CASCADE,
+  started_at timestamptz NOT NULL,
+  stopped_at timestamptz DEFAULT NULL,
+  CHECK (stopped_at IS NULL OR stopped_at >= started_at),
+  note varchar(500) DEFAULT NULL
+);
+
+CREATE INDEX IF NOT EXISTS worklog_task_id_idx ON worklogs(task_id);
+CREATE INDEX IF NOT EXISTS worklog_user_id_idx ON worklogs(user_id);
+-- only one running timer per adventurer and task
+CREATE UNIQUE INDEX IF NOT EXISTS worklog_running_idx ON worklogs(task_id, user_id) WHERE stopped_at IS NULL;
+
+
+-- deadlines
+
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS due_at timestamptz DEFAULT NULL;
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS overdue_at timestamptz DEFAULT NULL;
+
+CREATE INDEX IF NOT EXISTS due_at_idx ON tasks(due_at);
+CREATE INDEX IF NOT EXISTS overdue_at_idx ON tasks(overdue_at) WHERE overdue_at IS NOT NULL;
+
+
+-- campaigns
+
+CREATE TABLE IF NOT EXISTS campaigns (
+  id SERIAL PRIMARY KEY,
+  name varchar(120) NOT NULL,
+  goal varchar(1000) NOT NULL,
+  starts_on date NOT NULL,
+  ends_on date NOT NULL,
+  CHECK (ends_on >= starts_on)
+);
+
+CREATE INDEX IF NOT EXISTS campaign_dates_idx ON campaigns(starts_on, ends_on);
+
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS campaign_id INT DEFAULT NULL REFERENCES campaigns(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS campaign_id_idx ON tasks(campaign_id);
+
+
+-- quest chains
+
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS parent_id INT DEFAULT NULL REFERENCES tasks(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS parent_id_idx ON tasks(parent_id);
+
+CREATE TABLE IF NOT EXISTS task_dependencies (
+  task_id INT NOT NULL,
+  CONSTRAINT fk_tasks
+    FOREIGN KEY(task_id)
+	    REFERENCES tasks(id)
+	    ON DELETE CASCADE,
+  blocked_by INT NOT NULL,
+  CONSTRAINT fk_blocking_tasks
+    FOREIGN KEY(blocked_by)
+	    REFERENCES tasks(id)
+	    ON DELETE CASCADE,
+  CHECK (task_id <> blocked_by),
+  PRIMARY KEY (task_id, blocked_by)
+);
+
+CREATE INDEX IF NOT EXISTS blocked_by_idx ON task_dependencies(blocked_by);
+
+
+-- comments
+
+CREATE TABLE IF NOT EXISTS task_comments (
+  id SERIAL PRIMARY KEY,
+  task_id INT NOT NULL,
+  CONSTRAINT fk_tasks
+    FOREIGN KEY(task_id)
+	    REFERENCES tasks(id)
+	    ON DELETE CASCADE,
+  author_id INT DEFAULT NULL,
+  CONSTRAINT fk_users
+    FOREIGN KEY(author_id)
+	    REFERENCES users(id)
+	    ON DELETE SET NULL,
+  body varchar(5000) NOT NULL,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  updated_at timestamptz DEFAULT NULL
+);
+
+CREATE INDEX IF NOT EXISTS comment_task_id_idx ON task_comments(task_id, created_at);
+
+CREATE TABLE IF NOT EXISTS comment_mentions (
+  comment_id INT NOT NULL,
+  CONSTRAINT fk_comments
+    FOREIGN KEY(comment_id)
+	    REFERENCES task_comments(id)
+	    ON DELETE CASCADE,
+  user_id INT NOT NULL,
+  CONSTRAINT fk_users
+    FOREIGN KEY(user_id)
+	    REFERENCES users(id)
+	    ON DELETE CASCADE,
+  PRIMARY KEY (comment_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS mention_user_id_idx ON comment_mentions(user_id);
+
+
+-- attachments
+
+CREATE TABLE IF NOT EXISTS attachments (
+  id SERIAL PRIMARY KEY,
+  task_id INT NOT NULL,
+  CONSTRAINT fk_tasks
+    FOREIGN KEY(task_id)
+	    REFERENCES tasks(id)
+	    ON DELETE CASCADE,
+  uploader_id INT DEFAULT NULL,
+  CONSTRAINT fk_users
+    FOREIGN KEY(uploader_id)
+	    REFERENCES users(id)
+	    ON DELETE SET NULL,
+  file_name varchar(255) NOT NULL,
+  mime varchar(255) NOT NULL,
+  size bigint NOT NULL,
+  storage_key varchar(64) NOT NULL,
+  created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS attachment_task_id_idx ON attachments(task_id);
+
+
+-- search
+
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS search tsvector
+  GENERATED ALWAYS AS (to_tsvector('russian', description) || to_tsvector('english', description)) STORED;
+
+CREATE INDEX IF NOT EXISTS search_idx ON tasks USING GIN (search);
+CREATE INDEX IF NOT EXISTS expected_time_idx ON tasks(expected_time);
+
+
+-- tag catalog
+
+CREATE TABLE IF NOT EXISTS tags (
+  id SERIAL PRIMARY KEY,
+  name varchar(64) NOT NULL,
+  CHECK (name = btrim(name) AND name <> ''),
+  parent_id INT DEFAULT NULL,
+  CONSTRAINT fk_parent
+    FOREIGN KEY(parent_id)
+	    REFERENCES tags(id)
+	    ON DELETE SET NULL,
+  CHECK (parent_id <> id)
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS tag_name_idx ON tags(lower(name));
+CREATE INDEX IF NOT EXISTS tag_parent_id_idx ON tags(parent_id);
+
+-- synonyms are stored lowercased
+CREATE TABLE IF NOT EXISTS tag_synonyms (
+  synonym varchar(64) PRIMARY KEY,
+  CHECK (synonym = lower(btrim(synonym)) AND synonym <> ''),
+  tag_id INT NOT NULL,
+  CONSTRAINT fk_tags
+    FOREIGN KEY(tag_id)
+	    REFERENCES tags(id)
+	    ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS synonym_tag_id_idx ON tag_synonyms(tag_id);
+
+CREATE OR REPLACE FUNCTION canonical_tag(tag text) RETURNS text AS $$
+  SELECT COALESCE(
+    (SELECT name FROM tags WHERE lower(name) = lower(btrim(tag))),
+    (SELECT t.name FROM tag_synonyms s JOIN tags t ON t.id = s.tag_id WHERE s.synonym = lower(btrim(tag))),
+    btrim(tag)
+  )
+$$ LANGUAGE SQL STABLE;
+
+-- canonical names in the original order, without blanks and duplicates
+CREATE OR REPLACE FUNCTION normalize_tags(tags text[]) RETURNS text[] AS $$
+  SELECT COALESCE(array_agg(n.tag ORDER BY n.ord), '{}') FROM (
+    SELECT DISTINCT ON (lower(canonical_tag(u.tag))) canonical_tag(u.tag) AS tag, u.ord
+    FROM unnest(tags) WITH ORDINALITY u(tag, ord)
+    WHERE btrim(u.tag) <> ''
+    ORDER BY lower(canonical_tag(u.tag)), u.ord
+  ) n
+$$ LANGUAGE SQL STABLE;
+
+-- the tag with all its descendants
+CREATE OR REPLACE FUNCTION tag_subtree(tag text) RETURNS text[] AS $$
+  WITH RECURSIVE subtree AS (
+    SELECT id, name FROM tags WHERE lower(name) = lower(canonical_tag(tag))
+    UNION
+    SELECT t.id, t.name FROM tags t JOIN subtree s ON t.parent_id = s.id
+  )
+  SELECT COALESCE((SELECT array_agg(name) FROM subtree), ARRAY[canonical_tag(tag)])
+$$ LANGUAGE SQL STABLE;
+
+-- every tag in use gets into the catalog under its most common spelling,
+-- then tasks switch to canonical names
+INSERT INTO tags (name)
+  SELECT DISTINCT ON (lower(tag)) tag FROM (
+    SELECT btrim(tag) AS tag, COUNT(*) AS uses FROM (
+      SELECT unnest(tags) AS tag FROM tasks
+    ) used
+    WHERE btrim(tag) <> '' AND length(btrim(tag)) <= 64
+    GROUP BY btrim(tag)
+  ) spelled
+  WHERE NOT EXISTS (SELECT 1 FROM tag_synonyms WHERE synonym = lower(tag))
+  ORDER BY lower(tag), uses DESC, tag
+  ON CONFLICT DO NOTHING;
+
+UPDATE tasks SET tags = normalize_tags(tags) WHERE tags IS DISTINCT FROM normalize_tags(tags);
+
+
+-- recommendation feedback
+
+CREATE TABLE IF NOT EXISTS recommendation_feedback (
+  user_id INT NOT NULL,
+  CONSTRAINT fk_users
+    FOREIGN KEY(user_id)
+	    REFERENCES users(id)
+	    ON DELETE CASCADE,
+  task_id INT NOT NULL,
+  CONSTRAINT fk_tasks
+    FOREIGN KEY(task_id)
+	    REFERENCES tasks(id)
+	    ON DELETE CASCADE,
+  vote varchar(8) NOT NULL,
+  CHECK (vote IN ('up', 'down', 'hide')),
+  created_at timestamptz NOT NULL DEFAULT now(),
+  PRIMARY KEY (user_id, task_id)
+);
+
+-- what was recommended to whom and whether it was taken, to evaluate the recommender
+CREATE TABLE IF NOT EXISTS recommendation_impressions (
+  user_id INT NOT NULL,
+  CONSTRAINT fk_users
+    FOREIGN KEY(user_id)
+	    REFERENCES users(id)
+	    ON DELETE CASCADE,
+  task_id INT NOT NULL,
+  CONSTRAINT fk_tasks
+    FOREIGN KEY(task_id)
+	    REFERENCES tasks(id)
+	    ON DELETE CASCADE,
+  score real DEFAULT NULL,
+  shown_at timestamptz NOT NULL DEFAULT now(),
+  accepted_at timestamptz DEFAULT NULL,
+  PRIMARY KEY (user_id, task_id)
+);
+
+
+-- sessions
+
+-- outlive restarts and are shared by replicas
+CREATE TABLE IF NOT EXISTS sessions (
+  id text PRIMARY KEY,
+  data text NOT NULL,
+  expires_at timestamptz NOT NULL
+);
+ALTER INDEX IF EXISTS sessions_expires_at RENAME TO session_expires_at_idx;
+CREATE INDEX IF NOT EXISTS session_expires_at_idx ON sessions(expires_at);
+
+
+-- background jobs
+
+-- last run of each job, whichever replica ran it
+CREATE TABLE IF NOT EXISTS jobs (
+  name varchar(64) PRIMARY KEY,
+  schedule varchar(64) NOT NULL,
+  started_at timestamptz DEFAULT NULL,
+  finished_at timestamptz DEFAULT NULL,
+  succeeded boolean DEFAULT NULL,
+  report text DEFAULT NULL,
+  runs INT NOT NULL DEFAULT 0,
+  failures INT NOT NULL DEFAULT 0
+);
+
+-- daily leaderboard standings for every metric, to see how ranks change
+CREATE TABLE IF NOT EXISTS leaderboard_snapshots (
+  taken_on date NOT NULL,
+  metric varchar(16) NOT NULL,
+  user_id INT NOT NULL,
+  CONSTRAINT fk_users
+    FOREIGN KEY(user_id)
+	    REFERENCES users(id)
+	    ON DELETE CASCADE,
+  score float8 NOT NULL,
+  rank bigint NOT NULL,
+  PRIMARY KEY (taken_on, metric, user_id)
+);
+
+
+-- audit log
+
+-- who changed what and from where, rows are never updated or removed
+CREATE TABLE IF NOT EXISTS audit_log (
+  id SERIAL PRIMARY KEY,
+  at timestamptz NOT NULL DEFAULT now(),
+  -- no foreign key: the record outlives the user, the login is kept as it was
+  actor_id INT DEFAULT NULL,
+  actor_login varchar(36) DEFAULT NULL,
+  action varchar(32) NOT NULL,
+  target_type varchar(16) NOT NULL,
+  target_id INT DEFAULT NULL,
+  before jsonb DEFAULT NULL,
+  after jsonb DEFAULT NULL,
+  ip inet DEFAULT NULL
+);
+ALTER INDEX IF EXISTS audit_log_at RENAME TO audit_at_idx;
+ALTER INDEX IF EXISTS audit_log_target RENAME TO audit_target_idx;
+CREATE INDEX IF NOT EXISTS audit_at_idx ON audit_log(at);
+CREATE INDEX IF NOT EXISTS audit_target_idx ON audit_log(target_type, target_id);
+
+CREATE OR REPLACE FUNCTION audit_log_append_only() RETURNS trigger AS $$
+BEGIN
+  RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS audit_log_append_only ON audit_log;
+CREATE TRIGGER audit_log_append_only BEFORE UPDATE OR DELETE ON audit_log
+  FOR EACH ROW EXECUTE FUNCTION audit_log_append_only();
+DROP TRIGGER IF EXISTS audit_log_no_truncate ON audit_log;
+CREATE TRIGGER audit_log_no_truncate BEFORE TRUNCATE ON audit_log
+  FOR EACH STATEMENT EXECUTE FUNCTION audit_log_append_only();
+
+
+-- timestamps
+
+CREATE OR REPLACE FUNCTION touch_updated_at() RETURNS trigger AS $$
+BEGIN
+  NEW.updated_at = now();
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS created_at timestamptz;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS updated_at timestamptz;
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS created_at timestamptz;
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS updated_at timestamptz;
+ALTER TABLE invite_tokens ADD COLUMN IF NOT EXISTS created_at timestamptz;
+ALTER TABLE invite_tokens ADD COLUMN IF NOT EXISTS updated_at timestamptz;
+
+-- older rows get the earliest moment they are known to have existed, the migration time when nothing is known
+UPDATE users SET created_at = COALESCE(LEAST(
+    (SELECT MIN(completed_at) FROM completed_tasks WHERE user_id = users.id),
+    (SELECT MIN(started_at) FROM worklogs WHERE user_id = users.id)
+  ), now())
+  WHERE created_at IS NULL;
+UPDATE tasks SET created_at = COALESCE(LEAST(
+    assigned_at,
+    (SELECT completed_at FROM completed_tasks WHERE task_id = tasks.id),
+    (SELECT MIN(started_at) FROM worklogs WHERE task_id = tasks.id)
+  ), now())
+  WHERE created_at IS NULL;
+UPDATE invite_tokens SET created_at = now() WHERE created_at IS NULL;
+UPDATE users SET updated_at = created_at WHERE updated_at IS NULL;
+UPDATE tasks SET updated_at = created_at WHERE updated_at IS NULL;
+UPDATE invite_tokens SET updated_at = created_at WHERE updated_at IS NULL;
+
+ALTER TABLE users ALTER COLUMN created_at SET DEFAULT now(), ALTER COLUMN created_at SET NOT NULL,
+  ALTER COLUMN updated_at SET DEFAULT now(), ALTER COLUMN updated_at SET NOT NULL;
+ALTER TABLE tasks ALTER COLUMN created_at SET DEFAULT now(), ALTER COLUMN created_at SET NOT NULL,
+  ALTER COLUMN updated_at SET DEFAULT now(), ALTER COLUMN updated_at SET NOT NULL;
+ALTER TABLE invite_tokens ALTER COLUMN created_at SET DEFAULT now(), ALTER COLUMN created_at SET NOT NULL,
+  ALTER COLUMN updated_at SET DEFAULT now(), ALTER COLUMN updated_at SET NOT NULL;
+
+DROP TRIGGER IF EXISTS users_updated_at ON users;
+CREATE TRIGGER users_updated_at BEFORE UPDATE ON users
+  FOR EACH ROW EXECUTE FUNCTION touch_updated_at();
+DROP TRIGGER IF EXISTS tasks_updated_at ON tasks;
+CREATE TRIGGER tasks_updated_at BEFORE UPDATE ON tasks
+  FOR EACH ROW EXECUTE FUNCTION touch_updated_at();
+DROP TRIGGER IF EXISTS invite_tokens_updated_at ON invite_tokens;
+CREATE TRIGGER invite_tokens_updated_at BEFORE UPDATE ON invite_tokens
+  FOR EACH ROW EXECUTE FUNCTION touch_updated_at();
+
+-- completed_tasks rows are written once, completed_at is their creation time
+
+-- the board shows the newest quests first
+DROP INDEX IF EXISTS tasks_created_at_idx;
+CREATE INDEX IF NOT EXISTS created_at_idx ON tasks(created_at DESC, id DESC);
@@ -8,6 +8,6 @@ export DB_PASSWORD_PATH=$PWD/db/db_password.txt
 export DB_INIT_PATH=$PWD/db/init.sql
 # rust
 export RUST_BACKTRACE=1
-export RUST_LOG=trace
+export RUST_LOG=info,server=trace,tower_http=debug
 
 cd server && cargo run
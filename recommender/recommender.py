@@ -2,17 +2,25 @@ import numpy as np
 import compress_fasttext
 
 
-def get_recommendations(worker, tasks):
+def get_ranking(worker, tasks):
+    """Indexes of the suitable tasks with their scores from 0 to 1, best first."""
     model = compress_fasttext.models.CompressedFastTextKeyedVectors.load('quantized_large_model.bin')
 
     def vectorize_tags(tag_rows):
         vectorized_tags = []
         for tag_row in tag_rows:
             arrays = []
+            weights = []
             for tag in tag_row:
+                # worker tags come weighted by proficiency: [tag, weight]
+                if isinstance(tag, (list, tuple)):
+                    tag, weight = tag[0], float(tag[1])
+                else:
+                    weight = 1.0
                 arrays.append(model[tag])
-            if arrays:
-                vectorized_tags.append(np.mean(np.array(arrays), axis=0))
+                weights.append(weight)
+            if arrays and sum(weights) > 0:
+                vectorized_tags.append(np.average(np.array(arrays), axis=0, weights=weights))
             else:
                 vectorized_tags.append(np.zeros(model.vector_size))
         return vectorized_tags
@@ -61,7 +69,36 @@ def get_recommendations(worker, tasks):
                 elif i == len(sorted_tasks) - 1:
                     sorted_tasks.append(task_index)
                     break
-    return [tasks[x] for x in sorted_tasks]
+    return [(x, float(max(0.0, min(1.0, sim[x][0])))) for x in sorted_tasks]
+
+
+UPVOTE_BONUS = 0.15
+DOWNVOTE_PENALTY = 0.3
+
+
+def apply_feedback(ranking, tasks, feedback):
+    """Moves tasks the worker voted for up or down, drops hidden ones.
+    Scores are kept as the model gave them."""
+    up = set(feedback.get('up', []))
+    down = set(feedback.get('down', []))
+    hide = set(feedback.get('hide', []))
+    adjusted = []
+    for index, score in ranking:
+        task_id = tasks[index][3]
+        if task_id in hide:
+            continue
+        rank_score = score
+        if task_id in up:
+            rank_score += UPVOTE_BONUS
+        elif task_id in down:
+            rank_score -= DOWNVOTE_PENALTY
+        adjusted.append((rank_score, index, score))
+    adjusted.sort(key=lambda x: -x[0])
+    return [(index, score) for _, index, score in adjusted]
+
+
+def get_recommendations(worker, tasks):
+    return [tasks[x] for x, _ in get_ranking(worker, tasks)]
 
 
 if __name__ == '__main__':
@@ -1,6 +1,6 @@
 import json
 from http.server import BaseHTTPRequestHandler, HTTPServer
-from recommender import get_recommendations
+from recommender import apply_feedback, get_ranking, get_recommendations
 
 
 class RequestHandler(BaseHTTPRequestHandler):
@@ -17,7 +17,15 @@ class RequestHandler(BaseHTTPRequestHandler):
         worker = data.get('worker')
         tasks = data.get('tasks')
 
-        recommendations = get_recommendations(worker, tasks)
+        # version 2 clients send task ids as the 4th element and want ids with scores back
+        if data.get('version', 1) >= 2:
+            top_n = data.get('top_n')
+            ranking = apply_feedback(get_ranking(worker, tasks), tasks, data.get('feedback', {}))[:top_n]
+            recommendations = {
+                'recommendations': [{'id': tasks[i][3], 'score': score} for i, score in ranking]
+            }
+        else:
+            recommendations = get_recommendations(worker, tasks)
 
         recommendations_serializable = self.make_serializable(recommendations)
 
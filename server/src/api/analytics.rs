@@ -0,0 +1,101 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+
+use crate::{
+    entities::analytics::{self, Filter},
+    libs::{auth::AuthSession, logging::LogErr},
+    AppState,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/throughput", get(throughput))
+        .route("/cycle_time", get(cycle_time))
+        .route("/estimates", get(estimates))
+        .route("/workload", get(workload))
+        .route("/distribution", get(distribution))
+}
+
+#[track_caller]
+fn to_json<T: Serialize>(result: Result<T, tokio_postgres::Error>) -> Response {
+    match result.log_err() {
+        Ok(v) => Json::from(v).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+async fn throughput(
+    session: AuthSession,
+    State(state): State<AppState>,
+    Query(filter): Query<Filter>,
+) -> impl IntoResponse {
+    let Some(u) = session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    if !u.is_admin {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    to_json(analytics::get_throughput(&state.pool.try_get().await.unwrap(), &filter).await)
+}
+
+async fn cycle_time(
+    session: AuthSession,
+    State(state): State<AppState>,
+    Query(filter): Query<Filter>,
+) -> impl IntoResponse {
+    let Some(u) = session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    if !u.is_admin {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    to_json(analytics::get_cycle_time(&state.pool.try_get().await.unwrap(), &filter).await)
+}
+
+async fn estimates(
+    session: AuthSession,
+    State(state): State<AppState>,
+    Query(filter): Query<Filter>,
+) -> impl IntoResponse {
+    let Some(u) = session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    if !u.is_admin {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    to_json(analytics::get_estimates(&state.pool.try_get().await.unwrap(), &filter).await)
+}
+
+async fn workload(
+    session: AuthSession,
+    State(state): State<AppState>,
+    Query(filter): Query<Filter>,
+) -> impl IntoResponse {
+    let Some(u) = session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    if !u.is_admin {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    to_json(analytics::get_workload(&state.pool.try_get().await.unwrap(), &filter).await)
+}
+
+async fn distribution(
+    session: AuthSession,
+    State(state): State<AppState>,
+    Query(filter): Query<Filter>,
+) -> impl IntoResponse {
+    let Some(u) = session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    if !u.is_admin {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    to_json(analytics::get_distribution(&state.pool.try_get().await.unwrap(), &filter).await)
+}
@@ -0,0 +1,252 @@
+use axum::{
+    extract::{DefaultBodyLimit, Multipart, Path, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse},
+    routing::get,
+    Router,
+};
+use nanoid::nanoid;
+use tera::Context;
+
+use crate::{
+    entities::{
+        attachment::{self, AttachmentCreateData},
+        task,
+    },
+    libs::{auth::AuthSession, config::Config, logging::LogErr},
+    AppState,
+};
+
+const ALLOWED_MIME: [&str; 12] = [
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "text/plain",
+    "text/markdown",
+    "text/csv",
+    "application/pdf",
+    "application/json",
+    "application/zip",
+    "application/x-zip-compressed",
+    "application/gzip",
+];
+const MAX_FILE_NAME_LEN: usize = 255;
+
+pub fn router(config: &Config) -> Router<AppState> {
+    Router::new()
+        .route(
+            "/task/:task_id",
+            get(list).post(upload).layer(DefaultBodyLimit::max(
+                // room for multipart boundaries and headers
+                config.storage.max_attachment_size + 16 * 1024,
+            )),
+        )
+        .route("/:attachment_id", get(download).delete(delete_attachment))
+}
+
+// keeps the name readable but safe to put into a header
+fn clean_file_name(name: &str) -> String {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let name: String = name
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"')
+        .take(MAX_FILE_NAME_LEN)
+        .collect();
+
+    if name.trim().is_empty() {
+        "file".to_owned()
+    } else {
+        name
+    }
+}
+
+fn content_disposition(name: &str) -> String {
+    let ascii: String = name
+        .chars()
+        .map(|c| if c.is_ascii() { c } else { '_' })
+        .collect();
+    let encoded: String = name
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect();
+
+    format!("attachment; filename=\"{ascii}\"; filename*=UTF-8''{encoded}")
+}
+
+async fn render_list(state: &AppState, session: AuthSession, task_id: i32) -> Html<String> {
+    let u = session.user.unwrap();
+
+    if let Ok(attachments) = attachment::get_by_task(&state.pool.try_get().await.unwrap(), task_id)
+        .await
+        .log_err()
+    {
+        let mut ctx = Context::new();
+        ctx.insert("user", &u);
+        ctx.insert("task_id", &task_id);
+        ctx.insert("attachments", &attachments);
+        ctx.insert("allowed_mime", &ALLOWED_MIME.join(","));
+        ctx.insert(
+            "max_size_mb",
+            &(state.config.storage.max_attachment_size / 1024 / 1024),
+        );
+        let r = state.template.render("attachments.html", &ctx).unwrap();
+
+        return Html::from(r);
+    }
+
+    Html::from("<p>Неожиданная ошибка судьбы</p>".to_owned())
+}
+
+async fn list(
+    session: AuthSession,
+    Path(task_id): Path<i32>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    if task::get(&state.pool.try_get().await.unwrap(), task_id)
+        .await
+        .is_err()
+    {
+        return (
+            StatusCode::NOT_FOUND,
+            Html::from("<p>Задание не найдено</p>"),
+        )
+            .into_response();
+    }
+
+    render_list(&state, session, task_id).await.into_response()
+}
+
+async fn upload(
+    session: AuthSession,
+    Path(task_id): Path<i32>,
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let u = session.user.as_ref().unwrap();
+
+    // attachments are visible to whoever can see the quest
+    if task::get(&state.pool.try_get().await.unwrap(), task_id)
+        .await
+        .is_err()
+    {
+        return (
+            StatusCode::NOT_FOUND,
+            Html::from("<p>Задание не найдено</p>"),
+        )
+            .into_response();
+    }
+
+    let mut field = loop {
+        match multipart.next_field().await.log_err() {
+            Ok(Some(field)) if field.name() == Some("file") => break field,
+            Ok(Some(_)) => continue,
+            Ok(None) => return Html::from("<p>Свиток не приложен</p>").into_response(),
+            Err(_) => return Html::from("<p>Свиток слишком тяжел</p>").into_response(),
+        }
+    };
+    let file_name = clean_file_name(field.file_name().unwrap_or_default());
+    let mime = field
+        .content_type()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    if !ALLOWED_MIME.contains(&mime.as_str()) {
+        return Html::from("<p>Такие свитки в гильдию не принимают</p>").into_response();
+    }
+    // read by chunks to stop at the limit instead of holding the whole body
+    let mut data = Vec::new();
+    loop {
+        match field.chunk().await.log_err() {
+            Ok(Some(chunk)) => {
+                if data.len() + chunk.len() > state.config.storage.max_attachment_size {
+                    return Html::from("<p>Свиток слишком тяжел</p>").into_response();
+                }
+                data.extend_from_slice(&chunk);
+            }
+            Ok(None) => break,
+            Err(_) => return Html::from("<p>Свиток слишком тяжел</p>").into_response(),
+        }
+    }
+    if data.is_empty() {
+        return Html::from("<p>Свиток пуст</p>").into_response();
+    }
+
+    let storage_key = nanoid!();
+    if state.storage.put(&storage_key, &data).await.is_err() {
+        return Html::from("<p>Неожиданная ошибка судьбы</p>").into_response();
+    }
+    let created = attachment::create(
+        &state.pool.try_get().await.unwrap(),
+        &AttachmentCreateData {
+            task_id,
+            uploader_id: u.id,
+            file_name: &file_name,
+            mime: &mime,
+            size: data.len() as i64,
+            storage_key: &storage_key,
+        },
+    )
+    .await;
+    if created.is_err() {
+        let _ = state.storage.delete(&storage_key).await.log_err();
+        return Html::from("<p>Неожиданная ошибка судьбы</p>").into_response();
+    }
+
+    render_list(&state, session, task_id).await.into_response()
+}
+
+async fn download(
+    session: AuthSession,
+    Path(attachment_id): Path<i32>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    if session.user.is_none() {
+        return Html::from("<p>Свитки гильдии доступны только авантюристам</p>").into_response();
+    }
+    let Ok(a) = attachment::get(&state.pool.try_get().await.unwrap(), attachment_id)
+        .await
+        .log_err()
+    else {
+        return Html::from("<p>Свиток не найден</p>").into_response();
+    };
+
+    match state.storage.get(&a.storage_key).await.log_err() {
+        Ok(data) => (
+            [
+                (header::CONTENT_TYPE, a.mime.to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    content_disposition(&a.file_name),
+                ),
+                (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_owned()),
+            ],
+            data,
+        )
+            .into_response(),
+        Err(_) => Html::from("<p>Свиток утерян</p>").into_response(),
+    }
+}
+
+async fn delete_attachment(
+    session: AuthSession,
+    Path(attachment_id): Path<i32>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let u = session.user.unwrap();
+    let db_client = state.pool.try_get().await.unwrap();
+
+    if let Ok(a) = attachment::get(&db_client, attachment_id).await.log_err() {
+        if a.uploader_id != Some(u.id) && !u.is_admin {
+            return Html::from("<p>Вы не можете сжечь чужой свиток</p>").into_response();
+        }
+        if attachment::delete(&db_client, attachment_id).await.is_ok() {
+            let _ = state.storage.delete(&a.storage_key).await.log_err();
+            return Html::from("<p>Свиток сожжен</p>").into_response();
+        }
+    }
+
+    Html::from("<p>Неожиданная ошибка судьбы</p>").into_response()
+}
@@ -0,0 +1,113 @@
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use serde::Deserialize;
+
+use crate::{
+    entities::audit::{self, Entry, Filter},
+    libs::{auth::AuthSession, csv, logging::LogErr},
+    AppState,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/export", get(export))
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum Format {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    #[serde(default)]
+    format: Format,
+}
+
+const COLUMNS: [&str; 10] = [
+    "id",
+    "at",
+    "actor_id",
+    "actor_login",
+    "action",
+    "target_type",
+    "target_id",
+    "before",
+    "after",
+    "ip",
+];
+
+fn to_csv(entries: &[Entry]) -> String {
+    let mut out = String::new();
+    csv::write_record(&mut out, COLUMNS);
+    for e in entries {
+        let opt = |v: Option<String>| v.unwrap_or_default();
+        csv::write_record(
+            &mut out,
+            [
+                e.id.to_string().as_str(),
+                &e.at.to_rfc3339(),
+                &opt(e.actor_id.map(|v| v.to_string())),
+                e.actor_login.as_deref().unwrap_or_default(),
+                &e.action,
+                &e.target_type,
+                &opt(e.target_id.map(|v| v.to_string())),
+                &opt(e.before.as_ref().map(|v| v.to_string())),
+                &opt(e.after.as_ref().map(|v| v.to_string())),
+                &opt(e.ip.map(|v| v.to_string())),
+            ],
+        );
+    }
+    out
+}
+
+/// The whole filtered log, for auditors who take it elsewhere.
+async fn export(
+    session: AuthSession,
+    State(state): State<AppState>,
+    Query(query): Query<ExportQuery>,
+    Query(filter): Query<Filter>,
+) -> impl IntoResponse {
+    let Some(u) = session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    if !u.is_admin {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let Ok(entries) = audit::get_all(&state.pool.try_get().await.unwrap(), &filter)
+        .await
+        .log_err()
+    else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    match query.format {
+        Format::Json => (
+            [(
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"audit.json\"",
+            )],
+            Json::from(entries),
+        )
+            .into_response(),
+        Format::Csv => (
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+                (
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"audit.csv\"",
+                ),
+            ],
+            to_csv(&entries),
+        )
+            .into_response(),
+    }
+}
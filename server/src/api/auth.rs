@@ -5,12 +5,21 @@ use axum::{
     routing::post,
     Form, Router,
 };
+use chrono::Utc;
 use password_auth::generate_hash;
 use serde::Deserialize;
+use serde_json::json;
 
 use crate::{
-    entities::{invite, user},
-    libs::auth::{AuthSession, Credentials},
+    entities::{
+        audit::{Action, Target},
+        invite, user,
+    },
+    libs::{
+        audit::Audit,
+        auth::{AuthSession, Credentials},
+        logging::LogErr,
+    },
     AppState,
 };
 
@@ -32,10 +41,12 @@ struct UserRegisterData {
 async fn signup(
     mut auth_session: AuthSession,
     State(state): State<AppState>,
+    audit: Audit,
     Form(payload): Form<UserRegisterData>,
 ) -> impl IntoResponse {
     let db_client = state.pool.try_get().await.unwrap();
     // verity invite token
+    // an unknown secret is not an error of ours
     let expired = match invite::is_expired(&db_client, &payload.secret).await {
         Ok(v) => v,
         Err(_) => return Html::from("<p>Священное слово заклинателя - ложно</p>").into_response(),
@@ -45,7 +56,7 @@ async fn signup(
             .into_response();
     }
     // try to create user
-    if let Ok(v) = user::exists(&db_client, &payload.login).await {
+    if let Ok(v) = user::exists(&db_client, &payload.login).await.log_err() {
         // check if login exists
         if v {
             return Html::from("<p>Такое имя уже принадлежит другому авантюристу</p>")
@@ -64,11 +75,27 @@ async fn signup(
             class: user::Class::C,
             is_admin: false,
             tags: vec![],
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
         };
-        if let Ok(created_user) = user::create(&db_client, u).await {
-            if let Ok(_) = auth_session.login(&created_user).await {
+        if let Ok(created_user) = user::create(&db_client, u).await.log_err() {
+            if auth_session.login(&created_user).await.log_err().is_ok() {
                 // expire the invite token
-                let _ = invite::expire(&db_client, &payload.secret).await;
+                let _ = invite::expire(&db_client, &payload.secret).await.log_err();
+                audit
+                    .record(
+                        &db_client,
+                        Some(&created_user),
+                        Action::SignedUp,
+                        Target::User(created_user.id),
+                        None,
+                        Some(json!({
+                            "login": created_user.login,
+                            "name": created_user.name,
+                            "class": created_user.class,
+                        })),
+                    )
+                    .await;
                 // redirect to index
                 let mut r = Html::from("").into_response();
                 r.headers_mut()
@@ -77,18 +104,32 @@ async fn signup(
             }
         }
     }
-    return Html::from("<p>Неожиданная ошибка судьбы</p>").into_response();
+    Html::from("<p>Неожиданная ошибка судьбы</p>").into_response()
 }
 
 async fn signin(
     mut auth_session: AuthSession,
+    State(state): State<AppState>,
+    audit: Audit,
     Form(creds): Form<Credentials>,
 ) -> impl IntoResponse {
-    let user = match auth_session.authenticate(creds.clone()).await {
+    let db_client = state.pool.try_get().await.unwrap();
+    let user = match auth_session.authenticate(creds.clone()).await.log_err() {
         Ok(Some(user)) => user,
         Ok(None) => {
+            // the password is never recorded, only who was tried
+            audit
+                .record(
+                    &db_client,
+                    None,
+                    Action::SignInFailed,
+                    Target::Login,
+                    None,
+                    Some(json!({ "login": creds.login() })),
+                )
+                .await;
             return Html::from("<p>Такого имени не существует или тайное слово - ложно</p>")
-                .into_response()
+                .into_response();
         }
         Err(_) => {
             return Html::from("<p>Такого имени не существует или тайное слово - ложно</p>")
@@ -96,9 +137,19 @@ async fn signin(
         }
     };
 
-    if let Err(_) = auth_session.login(&user).await {
+    if auth_session.login(&user).await.log_err().is_err() {
         return Html::from("<p>Неожиданная ошибка судьбы</p>").into_response();
     }
+    audit
+        .record(
+            &db_client,
+            Some(&user),
+            Action::SignedIn,
+            Target::User(user.id),
+            None,
+            None,
+        )
+        .await;
 
     let mut r = Html::from("").into_response();
     r.headers_mut()
@@ -106,9 +157,25 @@ async fn signin(
     r
 }
 
-pub async fn logout(mut auth_session: AuthSession) -> impl IntoResponse {
-    match auth_session.logout().await {
-        Ok(_) => {
+pub async fn logout(
+    mut auth_session: AuthSession,
+    State(state): State<AppState>,
+    audit: Audit,
+) -> impl IntoResponse {
+    match auth_session.logout().await.log_err() {
+        Ok(user) => {
+            if let Some(u) = user {
+                audit
+                    .record(
+                        &state.pool.try_get().await.unwrap(),
+                        Some(&u),
+                        Action::SignedOut,
+                        Target::User(u.id),
+                        None,
+                        None,
+                    )
+                    .await;
+            }
             let mut r = Html::from("").into_response();
             r.headers_mut()
                 .insert("HX-Redirect", HeaderValue::from_static("/welcome"));
@@ -0,0 +1,107 @@
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+
+use crate::{
+    entities::burndown::{self, Scope},
+    libs::{auth::AuthSession, csv, logging::LogErr},
+    AppState,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/export", get(export_burndown))
+        .route("/velocity/export", get(export_velocity))
+}
+
+fn csv_response(filename: &str, body: String) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_owned()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+/// Every day of the range, the days ahead have only the ideal line.
+async fn export_burndown(
+    session: AuthSession,
+    State(state): State<AppState>,
+    Query(scope): Query<Scope>,
+) -> impl IntoResponse {
+    if session.user.is_none() {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let Ok(report) = burndown::get_report(&state.pool.try_get().await.unwrap(), &scope)
+        .await
+        .log_err()
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let burndown = report.burndown;
+    let mut out = String::new();
+    csv::write_record(&mut out, ["day", "remaining", "ideal", "completed"]);
+    for (i, (day, ideal)) in burndown
+        .dates()
+        .into_iter()
+        .zip(burndown.ideal())
+        .enumerate()
+    {
+        let actual = burndown.days.get(i);
+        csv::write_record(
+            &mut out,
+            [
+                day.to_string().as_str(),
+                &actual.map(|d| d.remaining.to_string()).unwrap_or_default(),
+                &format!("{ideal:.2}"),
+                &actual.map(|d| d.completed.to_string()).unwrap_or_default(),
+            ],
+        );
+    }
+    csv_response("burndown.csv", out)
+}
+
+async fn export_velocity(
+    session: AuthSession,
+    State(state): State<AppState>,
+    Query(scope): Query<Scope>,
+) -> impl IntoResponse {
+    if session.user.is_none() {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let Ok(report) = burndown::get_report(&state.pool.try_get().await.unwrap(), &scope)
+        .await
+        .log_err()
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let mut out = String::new();
+    csv::write_record(
+        &mut out,
+        ["period", "starts_on", "committed", "completed", "tasks"],
+    );
+    for v in report.velocity {
+        csv::write_record(
+            &mut out,
+            [
+                &*v.label,
+                &v.starts_on.to_string(),
+                &v.committed.map(|c| c.to_string()).unwrap_or_default(),
+                &v.completed.to_string(),
+                &v.tasks.to_string(),
+            ],
+        );
+    }
+    csv_response("velocity.csv", out)
+}
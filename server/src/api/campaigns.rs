@@ -0,0 +1,118 @@
+use axum::{
+    extract::{Path, State},
+    response::{Html, IntoResponse},
+    routing::{patch, post},
+    Form, Router,
+};
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::{
+    entities::campaign::{self, CampaignCreateData},
+    libs::{auth::AuthSession, logging::LogErr},
+    AppState,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/edit", post(create))
+        .route("/manage/carry_over/:campaign_id", patch(carry_over))
+}
+
+#[derive(Deserialize)]
+struct CampaignCreateForm {
+    name: Box<str>,
+    goal: Box<str>,
+    starts_on: NaiveDate,
+    ends_on: NaiveDate,
+}
+
+async fn create(
+    session: AuthSession,
+    State(state): State<AppState>,
+    Form(payload): Form<CampaignCreateForm>,
+) -> impl IntoResponse {
+    let u = session.user.unwrap();
+
+    if !u.is_admin {
+        return Html::from("<p>Недостаточно прав для совершения заклинания</p>").into_response();
+    }
+    if payload.ends_on < payload.starts_on {
+        return Html::from("<p>Кампания не может закончиться раньше, чем начнется</p>")
+            .into_response();
+    }
+
+    if let Ok(c) = campaign::create(
+        &state.pool.try_get().await.unwrap(),
+        &CampaignCreateData {
+            name: payload.name,
+            goal: payload.goal,
+            starts_on: payload.starts_on,
+            ends_on: payload.ends_on,
+        },
+    )
+    .await
+    .log_err()
+    {
+        return Html::from(format!(
+            "
+            <div class='rpgui-container framed-golden' style='position: relative; max-width: 600px; display: flex; flex-direction: column; justify-content: space-evenly; margin: 5px;'>
+                <h1>{}</h1>
+                <p>Сроки: <font color='#ff0'>{} - {}</font></p>
+                <div>
+                    <hr>
+                    <p style='line-break: normal;'>{}</p>
+                </div>
+                <div class='rpgui-center'>
+                    <hr>
+                    <a href='/campaigns/{}'><button class='rpgui-button' type='button'><p>Доска кампании</p></button></a>
+                    <a href='/campaigns/{}/chronicle'><button class='rpgui-button' type='button'><p>Летопись</p></button></a>
+                </div>
+            </div>
+            ",
+            c.name,
+            c.starts_on.format("%d.%m.%Y"),
+            c.ends_on.format("%d.%m.%Y"),
+            c.goal,
+            c.id,
+            c.id
+        ))
+        .into_response();
+    }
+
+    Html::from("<p>Неожиданная ошибка судьбы</p>").into_response()
+}
+
+async fn carry_over(
+    session: AuthSession,
+    Path(campaign_id): Path<i32>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let u = session.user.unwrap();
+    let db_client = state.pool.try_get().await.unwrap();
+
+    if !u.is_admin {
+        return Html::from("<p>Недостаточно прав для совершения заклинания</p>").into_response();
+    }
+
+    match campaign::get_next(&db_client, campaign_id).await.log_err() {
+        Ok(Some(next)) => {
+            if let Ok(n) = campaign::carry_over(&db_client, campaign_id, next.id)
+                .await
+                .log_err()
+            {
+                return Html::from(format!(
+                    "<p>Перенесено заданий в кампанию \"{}\": {n}</p>",
+                    next.name
+                ))
+                .into_response();
+            }
+        }
+        Ok(None) => {
+            return Html::from("<p>Следующая кампания еще не объявлена</p>").into_response()
+        }
+        Err(_) => (),
+    }
+
+    Html::from("<p>Неожиданная ошибка судьбы</p>").into_response()
+}
@@ -0,0 +1,142 @@
+use axum::{
+    extract::{Path, State},
+    response::{Html, IntoResponse},
+    routing::{get, patch},
+    Form, Router,
+};
+use serde::Deserialize;
+use tera::Context;
+
+use crate::{
+    entities::comment,
+    libs::{auth::AuthSession, logging::LogErr, markdown},
+    AppState,
+};
+
+const MAX_COMMENT_LEN: usize = 5000;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/task/:task_id", get(thread).post(create))
+        .route("/edit/:comment_id", patch(update).delete(delete_comment))
+}
+
+#[derive(Deserialize)]
+struct CommentForm {
+    body: Box<str>,
+}
+
+impl CommentForm {
+    fn body(&self) -> Option<&str> {
+        let body = self.body.trim();
+        (!body.is_empty() && body.chars().count() <= MAX_COMMENT_LEN).then_some(body)
+    }
+}
+
+async fn thread(
+    session: AuthSession,
+    Path(task_id): Path<i32>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let u = session.user.unwrap();
+
+    if let Ok(comments) = comment::get_by_task(&state.pool.try_get().await.unwrap(), task_id)
+        .await
+        .log_err()
+    {
+        let mut ctx = Context::new();
+        ctx.insert("user", &u);
+        ctx.insert("task_id", &task_id);
+        ctx.insert("comments", &comments);
+        let r = state.template.render("comments.html", &ctx).unwrap();
+
+        return Html::from(r).into_response();
+    }
+
+    Html::from("<p>Неожиданная ошибка судьбы</p>").into_response()
+}
+
+async fn create(
+    session: AuthSession,
+    Path(task_id): Path<i32>,
+    State(state): State<AppState>,
+    Form(payload): Form<CommentForm>,
+) -> impl IntoResponse {
+    let u = session.user.unwrap();
+
+    let Some(body) = payload.body() else {
+        return Html::from("<p>Свиток пуст или слишком длинный</p>").into_response();
+    };
+    if let Ok(c) = comment::create(
+        &state.pool.try_get().await.unwrap(),
+        task_id,
+        u.id,
+        body,
+        &markdown::mentions(body),
+    )
+    .await
+    .log_err()
+    {
+        let mut ctx = Context::new();
+        ctx.insert("user", &u);
+        ctx.insert("comment", &c);
+        let r = state.template.render("comment.html", &ctx).unwrap();
+
+        return Html::from(r).into_response();
+    }
+
+    Html::from("<p>Неожиданная ошибка судьбы</p>").into_response()
+}
+
+async fn update(
+    session: AuthSession,
+    Path(comment_id): Path<i32>,
+    State(state): State<AppState>,
+    Form(payload): Form<CommentForm>,
+) -> impl IntoResponse {
+    let u = session.user.unwrap();
+    let db_client = state.pool.try_get().await.unwrap();
+
+    let Some(body) = payload.body() else {
+        return Html::from("<p>Свиток пуст или слишком длинный</p>").into_response();
+    };
+    if let Ok(c) = comment::get(&db_client, comment_id).await.log_err() {
+        // only the author may rewrite their words
+        if c.author_id != Some(u.id) {
+            return Html::from("<p>Вы не можете изменить чужой свиток</p>").into_response();
+        }
+        if let Ok(c) = comment::update(&db_client, comment_id, body, &markdown::mentions(body))
+            .await
+            .log_err()
+        {
+            let mut ctx = Context::new();
+            ctx.insert("user", &u);
+            ctx.insert("comment", &c);
+            let r = state.template.render("comment.html", &ctx).unwrap();
+
+            return Html::from(r).into_response();
+        }
+    }
+
+    Html::from("<p>Неожиданная ошибка судьбы</p>").into_response()
+}
+
+async fn delete_comment(
+    session: AuthSession,
+    Path(comment_id): Path<i32>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let u = session.user.unwrap();
+    let db_client = state.pool.try_get().await.unwrap();
+
+    if let Ok(c) = comment::get(&db_client, comment_id).await.log_err() {
+        if c.author_id != Some(u.id) && !u.is_admin {
+            return Html::from("<p>Вы не можете сжечь чужой свиток</p>").into_response();
+        }
+        if comment::delete(&db_client, comment_id).await.is_ok() {
+            return Html::from("<p>Свиток сожжен</p>").into_response();
+        }
+    }
+
+    Html::from("<p>Неожиданная ошибка судьбы</p>").into_response()
+}
@@ -0,0 +1,55 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+use tokio::join;
+
+use crate::{
+    entities::{
+        leaderboard::{self, Entry, Filter},
+        page::Cursor,
+    },
+    libs::auth::AuthSession,
+    AppState,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/", get(top))
+}
+
+#[derive(Serialize)]
+struct LeaderboardData {
+    entries: Vec<Entry>,
+    next_cursor: Option<i32>,
+    me: Option<Entry>,
+}
+
+async fn top(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Query(filter): Query<Filter>,
+    Query(cursor): Query<Cursor>,
+) -> impl IntoResponse {
+    let Some(u) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let db_client = state.pool.try_get().await.unwrap();
+
+    let (entries, me) = join!(
+        leaderboard::top(&db_client, &filter, &cursor),
+        leaderboard::rank_of(&db_client, &filter, u.id)
+    );
+    match (entries, me) {
+        (Ok(page), Ok(me)) => Json::from(LeaderboardData {
+            entries: page.items,
+            next_cursor: page.next_cursor,
+            me,
+        })
+        .into_response(),
+        _ => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
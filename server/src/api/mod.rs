@@ -1,19 +1,48 @@
 use crate::AppState;
 use axum::Router;
 
+mod analytics;
+mod attachments;
+mod audit;
 mod auth;
+mod burndown;
+mod campaigns;
+mod comments;
+mod leaderboard;
+mod monitoring;
 mod pages;
+mod recommendations;
+mod tags;
 mod tasks;
 mod token;
+mod transfer;
 
 pub fn api(state: AppState) -> Router<AppState> {
-    let api = Router::new()
+    let features = state.config.features;
+    let mut api = Router::new()
         .nest("/auth", auth::router())
+        .nest("/analytics", analytics::router())
+        .nest("/audit", audit::router())
+        .nest("/burndown", burndown::router())
         .nest("/token", token::router())
-        .nest("/task", tasks::router());
+        .nest("/leaderboard", leaderboard::router())
+        .nest("/task", tasks::router())
+        .nest("/campaign", campaigns::router())
+        .nest("/tag", tags::router())
+        .nest("/transfer", transfer::router());
+    if features.comments {
+        api = api.nest("/comment", comments::router());
+    }
+    if features.attachments {
+        api = api.nest("/attachment", attachments::router(state.config));
+    }
+    if features.recommendations {
+        api = api.nest("/recommendation", recommendations::router());
+    }
 
     Router::new()
-        .nest("/", pages::router())
+        .nest("/", pages::router(state.config))
+        .merge(monitoring::router())
         .nest("/api", api)
         .with_state(state)
 }
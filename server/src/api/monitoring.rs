@@ -0,0 +1,74 @@
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+
+use crate::{libs::logging::LogErr, AppState};
+
+// probes and scraping go around the login, they carry no user data
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(metrics))
+}
+
+async fn healthz() -> impl IntoResponse {
+    "ok"
+}
+
+#[derive(Serialize)]
+struct Readiness {
+    ready: bool,
+    /// "ok" or the reason the database is unreachable.
+    db: String,
+    templates: usize,
+    /// "ok", "paused" after repeated failures or "disabled", the board works without it.
+    recommender: &'static str,
+}
+
+async fn readyz(State(state): State<AppState>) -> impl IntoResponse {
+    let db = match state.pool.try_get().await.log_err() {
+        Ok(db_client) => db_client
+            .simple_query("SELECT 1")
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    let db_ok = db.is_ok();
+    let templates = state.template.get_template_names().count();
+    let recommender = if !state.config.features.recommendations {
+        "disabled"
+    } else if state.recommender.stats().is_some_and(|s| s.circuit_open) {
+        "paused"
+    } else {
+        "ok"
+    };
+    let ready = db_ok && templates > 0;
+
+    (
+        if ready {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        },
+        Json::from(Readiness {
+            ready,
+            db: db.err().unwrap_or("ok".to_owned()),
+            templates,
+            recommender,
+        }),
+    )
+}
+
+async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(state.pool, state.recommender.stats()),
+    )
+}
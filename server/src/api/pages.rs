@@ -1,6 +1,8 @@
+use std::collections::HashMap;
+
 use axum::{
-    extract::State,
-    response::{Html, IntoResponse},
+    extract::{Path, Query, State},
+    response::{Html, IntoResponse, Redirect, Response},
     routing::get,
     Router,
 };
@@ -10,19 +12,47 @@ use tokio::join;
 use tower_http::services::ServeDir;
 
 use crate::{
-    entities::{task, user},
+    entities::{
+        analytics,
+        audit::{self, Action},
+        burndown::{self, Scope},
+        campaign, comment, job,
+        leaderboard::{self, Filter},
+        page::Cursor,
+        recommendation, skill, tag,
+        task::{self, BoardFilter, Task},
+        transfer,
+        user::User,
+    },
     libs::{
-        ai,
+        ai::{Profile, Recommendation},
         auth::{AuthSession, Backend},
+        charts::{self, Series, GOLD, RED, SKY},
+        config::Config,
+        db::DbClient,
+        logging::LogErr,
     },
-    AppState, STATIC_PATH,
+    AppState,
 };
 
-pub fn router() -> Router<AppState> {
+pub fn router(config: &Config) -> Router<AppState> {
     let protected = Router::new()
         .route("/", get(index))
         .route("/tasks", get(tasks))
+        .route("/tasks/board", get(tasks_board))
+        .route("/tasks/assigned", get(tasks_assigned))
         .route("/profile", get(profile))
+        .route("/tags", get(tags))
+        .route("/campaigns", get(campaigns))
+        .route("/campaigns/:campaign_id", get(campaign_board))
+        .route("/campaigns/:campaign_id/chronicle", get(campaign_chronicle))
+        .route("/campaigns/:campaign_id/burndown", get(campaign_burndown))
+        .route("/burndown", get(period_burndown))
+        .route("/jobs", get(jobs))
+        .route("/analytics", get(analytics_page))
+        .route("/audit", get(audit_log))
+        .route("/audit/entries", get(audit_entries))
+        .route("/transfer", get(transfer_page))
         .route("/guideStart", get(guide_start))
         .route("/guideShelter", get(guide_shelter))
         .route("/guideQuestboard", get(guide_quest_board))
@@ -30,57 +60,123 @@ pub fn router() -> Router<AppState> {
         .route_layer(login_required!(Backend, login_url = "/welcome"));
 
     Router::new()
-        .nest_service("/dist", ServeDir::new(format!("{}/dist", *STATIC_PATH)))
+        .nest_service("/dist", ServeDir::new(config.static_path.join("dist")))
         .route("/welcome", get(welcome))
         .route("/signin", get(signin))
         .route("/signup", get(signout))
         .nest("/", protected)
 }
 
-async fn index(auth_session: AuthSession, State(state): State<AppState>) -> impl IntoResponse {
+async fn index(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Query(filter): Query<Filter>,
+) -> impl IntoResponse {
     let u = &auth_session.user.unwrap();
     let db_client = state.pool.try_get().await.unwrap();
+    let class_filter = Filter {
+        class: Some(u.class),
+        ..filter.clone()
+    };
     let mut ctx = Context::new();
 
-    if let Ok(users) = user::top_players(&db_client).await {
-        ctx.insert("top_users", &users);
+    let first_page = Cursor::default();
+    let (top, top_class, me) = join!(
+        leaderboard::top(&db_client, &filter, &first_page),
+        leaderboard::top(&db_client, &class_filter, &first_page),
+        leaderboard::rank_of(&db_client, &filter, u.id)
+    );
+    if let Ok(users) = top.log_err() {
+        ctx.insert("top_users", &users.items);
+    }
+    if let Ok(users) = top_class.log_err() {
+        ctx.insert("top_class_users", &users.items);
     }
-    if let Ok(users) = user::top_players_by_class(&db_client, u.class).await {
-        ctx.insert("top_class_users", &users);
+    if let Ok(Some(me)) = me.log_err() {
+        ctx.insert("me", &me);
     }
+    ctx.insert("filter", &filter);
 
     let r = state.template.render("inn.html", &ctx).unwrap();
 
     Html::from(r)
 }
 
-async fn tasks(auth_session: AuthSession, State(state): State<AppState>) -> impl IntoResponse {
+// tasks the recommender picked for the user
+async fn recommendations(
+    state: &AppState,
+    db_client: &DbClient<'_>,
+    u: &User,
+    tasks: &[Task],
+) -> Vec<Recommendation> {
+    if !state.config.features.recommendations {
+        return vec![];
+    }
+    let (time, complexity, skills, votes) = join!(
+        task::get_avg_duration(db_client, u.id),
+        task::get_avg_complexity(db_client, u.id),
+        skill::get_by_user(db_client, u.id),
+        recommendation::get_votes(db_client, u.id)
+    );
+    let profile = Profile {
+        user_id: u.id,
+        complexity: complexity.log_err().unwrap_or(0.0),
+        time: time.log_err().unwrap_or(5.0),
+        tags: skills
+            .log_err()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|s| (s.tag, s.score as f32))
+            .collect(),
+        votes: votes.log_err().unwrap_or_default(),
+    };
+    let found = state
+        .recommender
+        .recommend(&profile, tasks)
+        .await
+        .log_err()
+        .unwrap_or(vec![]);
+    if !found.is_empty() {
+        let shown: Vec<(i32, Option<f32>)> = found.iter().map(|r| (r.task_id, r.score)).collect();
+        let _ = recommendation::log_shown(db_client, u.id, &shown)
+            .await
+            .log_err();
+    }
+
+    found
+}
+
+async fn tasks(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Query(filter): Query<BoardFilter>,
+) -> impl IntoResponse {
     let u = &auth_session.user.unwrap();
     let db_client = state.pool.try_get().await.unwrap();
 
     let mut ctx = Context::new();
     ctx.insert("user", &u);
-    if let Ok(tasks) = task::get_available(&db_client).await {
-        ctx.insert("tasks", &tasks);
-        // get recommended
-        let (time, complexity) = join!(
-            task::get_avg_duration(&db_client, u.id),
-            task::get_avg_complexity(&db_client, u.id)
+    ctx.insert("filter", &filter);
+    if let Ok(page) = task::get_available(&db_client, &filter, &Cursor::default())
+        .await
+        .log_err()
+    {
+        ctx.insert(
+            "recommendations",
+            &recommendations(&state, &db_client, u, &page.items).await,
         );
-        let recommended_indexes = ai::get_recommended(
-            state.http_client,
-            complexity.unwrap_or(0.0),
-            time.unwrap_or(5.0),
-            u.tags.clone(),
-            &tasks,
-        )
+        ctx.insert("tasks", &page.items);
+        ctx.insert("next_cursor", &page.next_cursor);
+    }
+    if let Ok(page) = task::get_assigned(&db_client, u.id, &Cursor::default())
         .await
-        .unwrap_or(vec![]);
-        ctx.insert("recommended_indexes", &recommended_indexes);
+        .log_err()
+    {
+        ctx.insert("tasks_in_progress", &page.items);
+        ctx.insert("next_assigned_cursor", &page.next_cursor);
     }
-    if let Ok(tasks) = task::get_assigned(&db_client, u.id).await {
-        // get all
-        ctx.insert("tasks_in_progress", &tasks);
+    if let Ok(campaigns) = campaign::get_open(&db_client).await.log_err() {
+        ctx.insert("campaigns", &campaigns);
     }
 
     let r = state.template.render("questBoard.html", &ctx).unwrap();
@@ -88,20 +184,514 @@ async fn tasks(auth_session: AuthSession, State(state): State<AppState>) -> impl
     Html::from(r)
 }
 
+// quest cards alone, the board reloads them on filter changes and loads more on scroll
+async fn tasks_board(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Query(filter): Query<BoardFilter>,
+    Query(cursor): Query<Cursor>,
+) -> impl IntoResponse {
+    let u = &auth_session.user.unwrap();
+    let db_client = state.pool.try_get().await.unwrap();
+
+    let Ok(page) = task::get_available(&db_client, &filter, &cursor)
+        .await
+        .log_err()
+    else {
+        return Html::from("<p>Неожиданная ошибка судьбы</p>".to_owned());
+    };
+    let mut ctx = Context::new();
+    ctx.insert("user", &u);
+    ctx.insert("filter", &filter);
+    ctx.insert(
+        "recommendations",
+        &recommendations(&state, &db_client, u, &page.items).await,
+    );
+    ctx.insert("tasks", &page.items);
+    ctx.insert("next_cursor", &page.next_cursor);
+    if let Ok(campaigns) = campaign::get_open(&db_client).await.log_err() {
+        ctx.insert("campaigns", &campaigns);
+    }
+
+    Html::from(state.template.render("questCards.html", &ctx).unwrap())
+}
+
+async fn tasks_assigned(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Query(cursor): Query<Cursor>,
+) -> impl IntoResponse {
+    let u = &auth_session.user.unwrap();
+    let db_client = state.pool.try_get().await.unwrap();
+
+    let Ok(page) = task::get_assigned(&db_client, u.id, &cursor)
+        .await
+        .log_err()
+    else {
+        return Html::from("<p>Неожиданная ошибка судьбы</p>".to_owned());
+    };
+    let mut ctx = Context::new();
+    ctx.insert("user", &u);
+    ctx.insert("tasks_in_progress", &page.items);
+    ctx.insert("next_assigned_cursor", &page.next_cursor);
+
+    Html::from(
+        state
+            .template
+            .render("questCardsAssigned.html", &ctx)
+            .unwrap(),
+    )
+}
+
 async fn profile(auth_session: AuthSession, State(state): State<AppState>) -> impl IntoResponse {
     let u = &auth_session.user.unwrap();
-    let total = task::get_count(&state.pool.try_get().await.unwrap(), u.id)
+    let db_client = state.pool.try_get().await.unwrap();
+    let total = task::get_count(&db_client, u.id)
         .await
+        .log_err()
         .unwrap_or(-1);
 
     let mut ctx = Context::new();
     ctx.insert("completed_tasks", &total);
+    if let Ok(mentions) = comment::get_mentions_of(&db_client, u.id).await.log_err() {
+        ctx.insert("mentions", &mentions);
+    }
+    if let Ok(skills) = skill::get_by_user(&db_client, u.id).await.log_err() {
+        ctx.insert("skills", &skills);
+    }
     ctx.insert("user", &u);
     let r = state.template.render("shelter.html", &ctx).unwrap();
 
     Html::from(r).into_response()
 }
 
+async fn tags(auth_session: AuthSession, State(state): State<AppState>) -> impl IntoResponse {
+    let u = &auth_session.user.unwrap();
+    let db_client = state.pool.try_get().await.unwrap();
+
+    let mut ctx = Context::new();
+    ctx.insert("user", &u);
+    ctx.insert("is_admin", &u.is_admin);
+    if let Ok(tags) = tag::get_all(&db_client).await.log_err() {
+        ctx.insert("tags", &tags);
+    }
+    let r = state.template.render("tags.html", &ctx).unwrap();
+
+    Html::from(r)
+}
+
+async fn campaigns(auth_session: AuthSession, State(state): State<AppState>) -> impl IntoResponse {
+    let u = &auth_session.user.unwrap();
+    let db_client = state.pool.try_get().await.unwrap();
+
+    let mut ctx = Context::new();
+    ctx.insert("user", &u);
+    if let Ok(campaigns) = campaign::get_all(&db_client).await.log_err() {
+        ctx.insert("campaigns", &campaigns);
+    }
+    let r = state.template.render("campaigns.html", &ctx).unwrap();
+
+    Html::from(r)
+}
+
+async fn campaign_board(
+    auth_session: AuthSession,
+    Path(campaign_id): Path<i32>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let u = &auth_session.user.unwrap();
+    let db_client = state.pool.try_get().await.unwrap();
+
+    let c = match campaign::get(&db_client, campaign_id).await.log_err() {
+        Ok(c) => c,
+        Err(_) => return Redirect::to("/campaigns").into_response(),
+    };
+    let mut ctx = Context::new();
+    ctx.insert("user", &u);
+    ctx.insert("campaign", &c);
+    if let Ok(tasks) = campaign::get_tasks(&db_client, campaign_id).await.log_err() {
+        ctx.insert("tasks", &tasks);
+    }
+    let r = state.template.render("campaign.html", &ctx).unwrap();
+
+    Html::from(r).into_response()
+}
+
+async fn campaign_chronicle(
+    Path(campaign_id): Path<i32>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let db_client = state.pool.try_get().await.unwrap();
+
+    let c = match campaign::get(&db_client, campaign_id).await.log_err() {
+        Ok(c) => c,
+        Err(_) => return Redirect::to("/campaigns").into_response(),
+    };
+    let (summary, contributions) = join!(
+        campaign::get_summary(&db_client, campaign_id),
+        campaign::get_contributions(&db_client, campaign_id)
+    );
+    let mut ctx = Context::new();
+    ctx.insert("campaign", &c);
+    if let Ok(summary) = summary.log_err() {
+        ctx.insert("summary", &summary);
+    }
+    if let Ok(contributions) = contributions.log_err() {
+        ctx.insert("contributions", &contributions);
+    }
+    let r = state.template.render("chronicle.html", &ctx).unwrap();
+
+    Html::from(r).into_response()
+}
+
+async fn campaign_burndown(
+    auth_session: AuthSession,
+    Path(campaign_id): Path<i32>,
+    State(state): State<AppState>,
+    Query(scope): Query<Scope>,
+) -> impl IntoResponse {
+    let scope = Scope {
+        campaign_id: Some(campaign_id),
+        ..scope
+    };
+    burndown_page(&state, &auth_session.user.unwrap(), &scope).await
+}
+
+async fn period_burndown(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Query(scope): Query<Scope>,
+) -> impl IntoResponse {
+    burndown_page(&state, &auth_session.user.unwrap(), &scope).await
+}
+
+async fn burndown_page(state: &AppState, u: &User, scope: &Scope) -> Response {
+    let db_client = state.pool.try_get().await.unwrap();
+
+    let Ok(report) = burndown::get_report(&db_client, scope).await.log_err() else {
+        return Redirect::to("/campaigns").into_response();
+    };
+    let burndown = &report.burndown;
+    let mut ctx = Context::new();
+    ctx.insert("user", u);
+    ctx.insert("scope", scope);
+    ctx.insert("periods", &scope.periods());
+    ctx.insert(
+        "burndown_chart",
+        &charts::lines(
+            &burndown
+                .dates()
+                .iter()
+                .map(|d| d.format("%d.%m").to_string())
+                .collect::<Vec<_>>(),
+            &[
+                Series {
+                    name: "Идеал",
+                    color: SKY,
+                    values: burndown.ideal(),
+                },
+                Series {
+                    name: "Осталось, ч",
+                    color: GOLD,
+                    values: burndown.days.iter().map(|d| d.remaining).collect(),
+                },
+            ],
+        ),
+    );
+    let mut velocity = vec![Series {
+        name: "Выполнено, ч",
+        color: GOLD,
+        values: report.velocity.iter().map(|v| v.completed).collect(),
+    }];
+    if report.campaign.is_some() {
+        velocity.insert(
+            0,
+            Series {
+                name: "Запланировано, ч",
+                color: SKY,
+                values: report
+                    .velocity
+                    .iter()
+                    .map(|v| v.committed.unwrap_or_default())
+                    .collect(),
+            },
+        );
+    }
+    ctx.insert(
+        "velocity_chart",
+        &charts::columns(
+            &report
+                .velocity
+                .iter()
+                .map(|v| v.label.to_string())
+                .collect::<Vec<_>>(),
+            &velocity,
+        ),
+    );
+    if !report.velocity.is_empty() {
+        ctx.insert(
+            "average_velocity",
+            &(report.velocity.iter().map(|v| v.completed).sum::<f64>()
+                / report.velocity.len() as f64),
+        );
+    }
+    ctx.insert("report", &report);
+    let r = state.template.render("burndown.html", &ctx).unwrap();
+
+    Html::from(r).into_response()
+}
+
+async fn jobs(auth_session: AuthSession, State(state): State<AppState>) -> impl IntoResponse {
+    let u = &auth_session.user.unwrap();
+    if !u.is_admin {
+        return Redirect::to("/profile").into_response();
+    }
+    let db_client = state.pool.try_get().await.unwrap();
+
+    let mut ctx = Context::new();
+    ctx.insert("user", &u);
+    if let Ok(jobs) = job::get_all(&db_client).await.log_err() {
+        ctx.insert("jobs", &jobs);
+    }
+    let r = state.template.render("jobs.html", &ctx).unwrap();
+
+    Html::from(r).into_response()
+}
+
+async fn transfer_page(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let u = &auth_session.user.unwrap();
+    if !u.is_admin {
+        return Redirect::to("/profile").into_response();
+    }
+
+    let mut ctx = Context::new();
+    ctx.insert("user", &u);
+    ctx.insert("max_rows", &transfer::MAX_IMPORT_ROWS);
+    ctx.insert(
+        "tables",
+        &[
+            ("tasks", "Задания"),
+            ("completions", "Выполненные задания"),
+            ("users", "Авантюристы"),
+        ],
+    );
+    let r = state.template.render("transfer.html", &ctx).unwrap();
+
+    Html::from(r).into_response()
+}
+
+async fn analytics_page(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Query(filter): Query<analytics::Filter>,
+) -> impl IntoResponse {
+    let u = &auth_session.user.unwrap();
+    if !u.is_admin {
+        return Redirect::to("/profile").into_response();
+    }
+    let db_client = state.pool.try_get().await.unwrap();
+
+    let (throughput, cycle_time, estimates, workload, distribution) = join!(
+        analytics::get_throughput(&db_client, &filter),
+        analytics::get_cycle_time(&db_client, &filter),
+        analytics::get_estimates(&db_client, &filter),
+        analytics::get_workload(&db_client, &filter),
+        analytics::get_distribution(&db_client, &filter)
+    );
+    let bucket_format = match filter.period.unit() {
+        "month" => "%m.%Y",
+        _ => "%d.%m",
+    };
+
+    let mut ctx = Context::new();
+    ctx.insert("user", &u);
+    ctx.insert("filter", &filter);
+    if let Ok(rows) = throughput.log_err() {
+        let labels: Vec<_> = rows
+            .iter()
+            .map(|r| r.bucket.format(bucket_format).to_string())
+            .collect();
+        ctx.insert("created", &rows.iter().map(|r| r.created).sum::<i64>());
+        ctx.insert("completed", &rows.iter().map(|r| r.completed).sum::<i64>());
+        ctx.insert(
+            "throughput_chart",
+            &charts::columns(
+                &labels,
+                &[
+                    Series {
+                        name: "Создано",
+                        color: SKY,
+                        values: rows.iter().map(|r| r.created as f64).collect(),
+                    },
+                    Series {
+                        name: "Выполнено",
+                        color: GOLD,
+                        values: rows.iter().map(|r| r.completed as f64).collect(),
+                    },
+                ],
+            ),
+        );
+    }
+    if let Ok(rows) = cycle_time.log_err() {
+        let labels: Vec<_> = rows
+            .iter()
+            .map(|r| r.bucket.format(bucket_format).to_string())
+            .collect();
+        ctx.insert(
+            "cycle_time_chart",
+            &charts::columns(
+                &labels,
+                &[
+                    Series {
+                        name: "Медиана, ч",
+                        color: GOLD,
+                        values: rows.iter().map(|r| r.median_hours.unwrap_or(0.0)).collect(),
+                    },
+                    Series {
+                        name: "90%, ч",
+                        color: RED,
+                        values: rows.iter().map(|r| r.p90_hours.unwrap_or(0.0)).collect(),
+                    },
+                ],
+            ),
+        );
+    }
+    if let Ok(estimates) = estimates.log_err() {
+        ctx.insert(
+            "estimates_chart",
+            &charts::columns(
+                &estimates
+                    .bands
+                    .iter()
+                    .map(|b| b.label.to_owned())
+                    .collect::<Vec<_>>(),
+                &[Series {
+                    name: "Заданий",
+                    color: GOLD,
+                    values: estimates.bands.iter().map(|b| b.count as f64).collect(),
+                }],
+            ),
+        );
+        ctx.insert("estimates", &estimates);
+    }
+    if let Ok(workload) = workload.log_err() {
+        ctx.insert(
+            "workload_chart",
+            &charts::bars(
+                &workload
+                    .iter()
+                    .map(|w| (w.login.to_string(), w.hours))
+                    .collect::<Vec<_>>(),
+                GOLD,
+            ),
+        );
+        ctx.insert("workload", &workload);
+    }
+    if let Ok(distribution) = distribution.log_err() {
+        ctx.insert(
+            "tags_chart",
+            &charts::bars(
+                &distribution
+                    .tags
+                    .iter()
+                    .map(|s| (s.name.to_string(), s.count as f64))
+                    .collect::<Vec<_>>(),
+                SKY,
+            ),
+        );
+        ctx.insert(
+            "complexity_chart",
+            &charts::columns(
+                &distribution
+                    .complexity
+                    .iter()
+                    .map(|s| s.name.to_string())
+                    .collect::<Vec<_>>(),
+                &[Series {
+                    name: "Выполнено",
+                    color: GOLD,
+                    values: distribution
+                        .complexity
+                        .iter()
+                        .map(|s| s.count as f64)
+                        .collect(),
+                }],
+            ),
+        );
+    }
+    let r = state.template.render("analytics.html", &ctx).unwrap();
+
+    Html::from(r).into_response()
+}
+
+fn audit_context(u: &User, filter: &audit::Filter) -> Context {
+    let mut ctx = Context::new();
+    ctx.insert("user", u);
+    ctx.insert("filter", filter);
+    ctx.insert(
+        "actions",
+        &Action::ALL.map(|a| (a.as_str(), a.label())).to_vec(),
+    );
+    ctx.insert(
+        "action_labels",
+        &Action::ALL
+            .iter()
+            .map(|a| (a.as_str(), a.label()))
+            .collect::<HashMap<_, _>>(),
+    );
+    ctx
+}
+
+async fn audit_log(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Query(filter): Query<audit::Filter>,
+) -> impl IntoResponse {
+    let u = &auth_session.user.unwrap();
+    if !u.is_admin {
+        return Redirect::to("/profile").into_response();
+    }
+    let db_client = state.pool.try_get().await.unwrap();
+
+    let mut ctx = audit_context(u, &filter);
+    if let Ok(page) = audit::get(&db_client, &filter, &Cursor::default())
+        .await
+        .log_err()
+    {
+        ctx.insert("entries", &page.items);
+        ctx.insert("next_cursor", &page.next_cursor);
+    }
+    let r = state.template.render("audit.html", &ctx).unwrap();
+
+    Html::from(r).into_response()
+}
+
+// entries alone, for filter changes and scrolling
+async fn audit_entries(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Query(filter): Query<audit::Filter>,
+    Query(cursor): Query<Cursor>,
+) -> impl IntoResponse {
+    let u = &auth_session.user.unwrap();
+    if !u.is_admin {
+        return Html::from("<p>Недостаточно прав для совершения заклинания</p>".to_owned());
+    }
+    let db_client = state.pool.try_get().await.unwrap();
+
+    let Ok(page) = audit::get(&db_client, &filter, &cursor).await.log_err() else {
+        return Html::from("<p>Неожиданная ошибка судьбы</p>".to_owned());
+    };
+    let mut ctx = audit_context(u, &filter);
+    ctx.insert("entries", &page.items);
+    ctx.insert("next_cursor", &page.next_cursor);
+
+    Html::from(state.template.render("auditEntries.html", &ctx).unwrap())
+}
+
 async fn guide_start(State(state): State<AppState>) -> impl IntoResponse {
     let ctx = Context::new();
     let r = state.template.render("guideStart.html", &ctx).unwrap();
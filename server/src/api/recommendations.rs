@@ -0,0 +1,104 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse},
+    routing::{get, put},
+    Form, Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use tera::Context;
+
+use crate::{
+    entities::recommendation::{self, Acceptance, Vote},
+    libs::{ai::Stats, auth::AuthSession, logging::LogErr},
+    AppState,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/stats", get(stats))
+        .route("/feedback/:task_id", put(vote).delete(unvote))
+}
+
+#[derive(Serialize)]
+struct Report {
+    recommender: Option<Stats>,
+    acceptance: Acceptance,
+}
+
+async fn stats(session: AuthSession, State(state): State<AppState>) -> impl IntoResponse {
+    if !session.user.is_some_and(|u| u.is_admin) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    match recommendation::get_acceptance(&state.pool.try_get().await.unwrap())
+        .await
+        .log_err()
+    {
+        Ok(acceptance) => Json::from(Report {
+            recommender: state.recommender.stats(),
+            acceptance,
+        })
+        .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct VoteForm {
+    vote: Vote,
+}
+
+fn render_feedback(state: &AppState, task_id: i32, vote: Option<Vote>) -> Html<String> {
+    let mut ctx = Context::new();
+    ctx.insert("task_id", &task_id);
+    ctx.insert("vote", &vote);
+    let r = state
+        .template
+        .render("recommendationFeedback.html", &ctx)
+        .unwrap();
+
+    Html::from(r)
+}
+
+async fn vote(
+    session: AuthSession,
+    Path(task_id): Path<i32>,
+    State(state): State<AppState>,
+    Form(payload): Form<VoteForm>,
+) -> impl IntoResponse {
+    let u = session.user.unwrap();
+
+    if recommendation::vote(
+        &state.pool.try_get().await.unwrap(),
+        u.id,
+        task_id,
+        payload.vote,
+    )
+    .await
+    .log_err()
+    .is_ok()
+    {
+        return render_feedback(&state, task_id, Some(payload.vote)).into_response();
+    }
+
+    Html::from("<p>Неожиданная ошибка судьбы</p>").into_response()
+}
+
+async fn unvote(
+    session: AuthSession,
+    Path(task_id): Path<i32>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let u = session.user.unwrap();
+
+    if recommendation::unvote(&state.pool.try_get().await.unwrap(), u.id, task_id)
+        .await
+        .log_err()
+        .is_ok()
+    {
+        return render_feedback(&state, task_id, None).into_response();
+    }
+
+    Html::from("<p>Неожиданная ошибка судьбы</p>").into_response()
+}
@@ -0,0 +1,214 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::{Html, IntoResponse},
+    routing::{delete, get, patch, post},
+    Form, Router,
+};
+use serde::Deserialize;
+use tera::Context;
+
+use crate::{
+    entities::tag::{self, Tag, MAX_TAG_LEN},
+    libs::{auth::AuthSession, db::DbClient, logging::LogErr},
+    AppState,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/suggest", get(suggest))
+        .route("/edit", post(create))
+        .route("/manage/parent/:tag_id", patch(set_parent))
+        .route("/manage/synonym/:tag_id", post(add_synonym))
+        .route("/manage/synonym/:tag_id/:synonym", delete(remove_synonym))
+}
+
+#[derive(Deserialize)]
+struct SuggestQuery {
+    tags: Box<str>,
+}
+
+// options for a datalist, each one completes the last word of the input
+async fn suggest(
+    session: AuthSession,
+    State(state): State<AppState>,
+    Query(query): Query<SuggestQuery>,
+) -> impl IntoResponse {
+    if session.user.is_none() {
+        return Html::from(String::new());
+    }
+    let input = query.tags.trim_start();
+    let (head, last) = match input.rsplit_once(char::is_whitespace) {
+        Some((head, last)) => (format!("{head} "), last),
+        None => (String::new(), input),
+    };
+    if last.is_empty() {
+        return Html::from(String::new());
+    }
+
+    let names = tag::suggest(&state.pool.try_get().await.unwrap(), last)
+        .await
+        .log_err()
+        .unwrap_or_default();
+    let mut ctx = Context::new();
+    ctx.insert("head", &head);
+    ctx.insert("names", &names);
+
+    Html::from(state.template.render("tagSuggestions.html", &ctx).unwrap())
+}
+
+fn render_tag(state: &AppState, tag: &Tag) -> Html<String> {
+    let mut ctx = Context::new();
+    ctx.insert("tag", tag);
+    ctx.insert("is_admin", &true);
+
+    Html::from(state.template.render("tag.html", &ctx).unwrap())
+}
+
+// empty name clears the parent, unknown name is an error
+async fn parent_id(db_client: &DbClient<'_>, name: &str) -> Result<Option<i32>, ()> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Ok(None);
+    }
+    match tag::find(db_client, name).await.log_err() {
+        Ok(Some(t)) => Ok(Some(t.id)),
+        _ => Err(()),
+    }
+}
+
+#[derive(Deserialize)]
+struct TagCreateForm {
+    name: Box<str>,
+    parent: Box<str>,
+}
+
+async fn create(
+    session: AuthSession,
+    State(state): State<AppState>,
+    Form(payload): Form<TagCreateForm>,
+) -> impl IntoResponse {
+    let u = session.user.unwrap();
+    let db_client = state.pool.try_get().await.unwrap();
+
+    if !u.is_admin {
+        return Html::from("<p>Недостаточно прав для совершения заклинания</p>").into_response();
+    }
+    let name = payload.name.trim();
+    if name.is_empty() || name.contains(char::is_whitespace) || name.chars().count() > MAX_TAG_LEN {
+        return Html::from("<p>Тэг должен быть одним словом</p>").into_response();
+    }
+    if let Ok(Some(_)) = tag::find(&db_client, name).await.log_err() {
+        return Html::from("<p>Такой тэг уже известен гильдии</p>").into_response();
+    }
+    let Ok(parent_id) = parent_id(&db_client, &payload.parent).await else {
+        return Html::from("<p>Родительский тэг не найден</p>").into_response();
+    };
+
+    match tag::create(&db_client, name, parent_id).await.log_err() {
+        Ok(t) => render_tag(&state, &t).into_response(),
+        Err(_) => Html::from("<p>Неожиданная ошибка судьбы</p>").into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct ParentForm {
+    parent: Box<str>,
+}
+
+async fn set_parent(
+    session: AuthSession,
+    Path(tag_id): Path<i32>,
+    State(state): State<AppState>,
+    Form(payload): Form<ParentForm>,
+) -> impl IntoResponse {
+    let u = session.user.unwrap();
+    let db_client = state.pool.try_get().await.unwrap();
+
+    if !u.is_admin {
+        return Html::from("<p>Недостаточно прав для совершения заклинания</p>").into_response();
+    }
+    let Ok(parent_id) = parent_id(&db_client, &payload.parent).await else {
+        return Html::from("<p>Родительский тэг не найден</p>").into_response();
+    };
+    if let Some(p) = parent_id {
+        if tag::would_cycle(&db_client, tag_id, p)
+            .await
+            .log_err()
+            .unwrap_or(true)
+        {
+            return Html::from("<p>Тэг не может быть предком самого себя</p>").into_response();
+        }
+    }
+
+    if tag::set_parent(&db_client, tag_id, parent_id).await.is_ok() {
+        if let Ok(t) = tag::get(&db_client, tag_id).await.log_err() {
+            return render_tag(&state, &t).into_response();
+        }
+    }
+
+    Html::from("<p>Неожиданная ошибка судьбы</p>").into_response()
+}
+
+#[derive(Deserialize)]
+struct SynonymForm {
+    synonym: Box<str>,
+}
+
+async fn add_synonym(
+    session: AuthSession,
+    Path(tag_id): Path<i32>,
+    State(state): State<AppState>,
+    Form(payload): Form<SynonymForm>,
+) -> impl IntoResponse {
+    let u = session.user.unwrap();
+    let mut db_client = state.pool.try_get().await.unwrap();
+
+    if !u.is_admin {
+        return Html::from("<p>Недостаточно прав для совершения заклинания</p>").into_response();
+    }
+    let synonym = payload.synonym.trim();
+    if synonym.is_empty()
+        || synonym.contains(char::is_whitespace)
+        || synonym.chars().count() > MAX_TAG_LEN
+    {
+        return Html::from("<p>Синоним должен быть одним словом</p>").into_response();
+    }
+
+    match tag::add_synonym(&mut db_client, tag_id, synonym)
+        .await
+        .log_err()
+    {
+        Ok(false) => {
+            return Html::from("<p>Тэг не может быть синонимом самого себя</p>").into_response()
+        }
+        Ok(true) => {
+            if let Ok(t) = tag::get(&db_client, tag_id).await.log_err() {
+                return render_tag(&state, &t).into_response();
+            }
+        }
+        Err(_) => (),
+    }
+
+    Html::from("<p>Неожиданная ошибка судьбы</p>").into_response()
+}
+
+async fn remove_synonym(
+    session: AuthSession,
+    Path((tag_id, synonym)): Path<(i32, Box<str>)>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let u = session.user.unwrap();
+    let db_client = state.pool.try_get().await.unwrap();
+
+    if !u.is_admin {
+        return Html::from("<p>Недостаточно прав для совершения заклинания</p>").into_response();
+    }
+
+    if tag::remove_synonym(&db_client, &synonym).await.is_ok() {
+        if let Ok(t) = tag::get(&db_client, tag_id).await.log_err() {
+            return render_tag(&state, &t).into_response();
+        }
+    }
+
+    Html::from("<p>Неожиданная ошибка судьбы</p>").into_response()
+}
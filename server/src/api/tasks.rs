@@ -1,17 +1,29 @@
+use std::num::ParseIntError;
+
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
+    http::StatusCode,
     response::{Html, IntoResponse},
-    routing::{delete, patch, post},
-    Form, Router,
+    routing::{delete, get, patch, post},
+    Form, Json, Router,
 };
-use serde::Deserialize;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 
 use crate::{
     entities::{
-        task::{self, TaskCreateData},
+        attachment,
+        audit::{Action, Target},
+        dependency,
+        page::Cursor,
+        recommendation,
+        tag::{self, MAX_TAG_LEN},
+        task::{self, BoardFilter, Task, TaskCreateData},
         user::Class,
+        worklog,
     },
-    libs::auth::AuthSession,
+    libs::{audit::Audit, auth::AuthSession, logging::LogErr, metrics::TaskEvent},
     AppState,
 };
 
@@ -19,9 +31,48 @@ pub fn router() -> Router<AppState> {
     Router::new()
         .route("/edit", post(create))
         .route("/edit/:task_id", delete(delete_task))
+        .route("/search", get(search))
         .route("/manage/assign/:task_id", patch(assign_to))
         .route("/manage/resign/:task_id", patch(resign))
         .route("/manage/complete/:task_id", patch(complete))
+        .route("/manage/timer/start/:task_id", patch(start_timer))
+        .route("/manage/timer/pause/:task_id", patch(pause_timer))
+        .route("/manage/worklog/:task_id", post(add_worklog))
+        .route("/manage/campaign/:task_id", patch(set_campaign))
+        .route("/manage/parent/:task_id", patch(set_parent))
+        .route("/manage/dependency/:task_id", post(add_dependency))
+        .route(
+            "/manage/dependency/:task_id/:blocked_by",
+            delete(remove_dependency),
+        )
+}
+
+#[derive(Serialize)]
+struct SearchData {
+    tasks: Vec<Task>,
+    next_cursor: Option<i32>,
+}
+
+async fn search(
+    session: AuthSession,
+    State(state): State<AppState>,
+    Query(filter): Query<BoardFilter>,
+    Query(cursor): Query<Cursor>,
+) -> impl IntoResponse {
+    if session.user.is_none() {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    match task::get_available(&state.pool.try_get().await.unwrap(), &filter, &cursor)
+        .await
+        .log_err()
+    {
+        Ok(page) => Json::from(SearchData {
+            tasks: page.items,
+            next_cursor: page.next_cursor,
+        })
+        .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
 }
 
 #[derive(Deserialize)]
@@ -30,11 +81,23 @@ struct TaskCreateForm {
     description: Box<str>,
     complexity: i16,
     expected_time: f32,
+    due_at: Option<Box<str>>,
+    campaign_id: Option<Box<str>>,
+    parent_id: Option<Box<str>>,
+}
+
+// an empty form field means none, anything else has to be an id
+fn optional_id(value: Option<&str>) -> Result<Option<i32>, ParseIntError> {
+    match value.map(str::trim).filter(|v| !v.is_empty()) {
+        Some(v) => v.parse().map(Some),
+        None => Ok(None),
+    }
 }
 
 async fn create(
     session: AuthSession,
     State(state): State<AppState>,
+    audit: Audit,
     Form(payload): Form<TaskCreateForm>,
 ) -> impl IntoResponse {
     let u = session.user.unwrap();
@@ -43,24 +106,73 @@ async fn create(
         return Html::from("<p>Недостаточно прав для совершения заклинания</p>").into_response();
     }
 
+    // due date is the end of the chosen day
+    let due_at = match payload.due_at.as_deref().filter(|d| !d.is_empty()) {
+        Some(d) => match NaiveDate::parse_from_str(d, "%Y-%m-%d") {
+            Ok(d) => d.and_hms_opt(23, 59, 59).map(|d| d.and_utc()),
+            Err(_) => return Html::from("<p>Неверная дата завершения</p>").into_response(),
+        },
+        None => None,
+    };
+    let Ok(campaign_id) = optional_id(payload.campaign_id.as_deref()) else {
+        return Html::from("<p>Неверный номер кампании</p>").into_response();
+    };
+    let Ok(parent_id) = optional_id(payload.parent_id.as_deref()) else {
+        return Html::from("<p>Неверный номер родительского задания</p>").into_response();
+    };
+
+    let db_client = state.pool.try_get().await.unwrap();
+    let tags = tag::normalize(&db_client, &payload.tags).await;
+    if let Err(tag::Error::TooLong) = tags {
+        return Html::from(format!(
+            "<p>Тэг не может быть длиннее {MAX_TAG_LEN} символов</p>"
+        ))
+        .into_response();
+    }
+    let Ok(tags) = tags.log_err() else {
+        return Html::from("<p>Неожиданная ошибка судьбы</p>").into_response();
+    };
+    if tags.is_empty() {
+        return Html::from("<p>Заданию нужен хотя бы один тэг</p>").into_response();
+    }
+
     if let Ok(task) = task::create(
-        &state.pool.try_get().await.unwrap(),
+        &db_client,
         &TaskCreateData {
             complexity: Class::from(payload.complexity),
             expected_time: payload.expected_time,
             description: payload.description,
-            tags: payload.tags.split(" ").map(|v| v.into()).collect(),
+            tags,
+            due_at,
+            campaign_id,
+            parent_id,
         },
     )
     .await
+    .log_err()
     {
+        state.metrics.record(TaskEvent::Created);
+        audit
+            .record(
+                &db_client,
+                Some(&u),
+                Action::TaskCreated,
+                Target::Task(task.id),
+                None,
+                serde_json::to_value(&task).ok(),
+            )
+            .await;
         // f this template lib not allowing me to do this
         return Html::from(format!("
             <div class='rpgui-container framed-golden' style='position: relative; max-width: 600px; margin-bottom: 20px; display: flex; flex-direction: column; justify-content: space-evenly; margin: 5px;'>
                 <h1 style='color: #ff0; display: none;'>Выполняется</h1>
+                <p>Задание <font color='#ff0'>№{}</font></p>
+                {}
                 <p>Тэги: <font color='#ff0'>[{}]</font></p>
                 <p>Рекомендуемый класс авантюриста: <font color='#ff0'>{}</font></p>
                 <p>Ожидаемое время выполнения в часах: <font color='#ff0'>{}</font></p>
+                <p>Опубликовано: <font color='#ff0'>{}</font></p>
+                {}
                 <div>
                     <hr>
                     <p sytle='line-break: normal;'>{}</p>
@@ -72,22 +184,27 @@ async fn create(
                 </div>
             </div>
         ",
+        task.id,
+        task.parent_id.map(|p| format!("<p>Надзадание: <font color='#ff0'>№{p}</font></p>")).unwrap_or_default(),
         task.tags.join(","),
         task.complexity,
         task.expected_time,
+        task.created_at.format("%d.%m.%Y"),
+        task.due_at.map(|d| format!("<p>Срок: <font color='#ff0'>{}</font></p>", d.format("%d.%m.%Y"))).unwrap_or_default(),
         task.description,
         task.id,
         task.id
         )).into_response();
     }
 
-    return Html::from("<p>Неожиданная ошибка судьбы</p>").into_response();
+    Html::from("<p>Неожиданная ошибка судьбы</p>").into_response()
 }
 
 async fn delete_task(
     session: AuthSession,
     Path(task_id): Path<i32>,
     State(state): State<AppState>,
+    audit: Audit,
 ) -> impl IntoResponse {
     let u = session.user.unwrap();
 
@@ -95,91 +212,546 @@ async fn delete_task(
         return Html::from("<p>Недостаточно прав для совершения заклинания</p>").into_response();
     }
 
-    if let Ok(_) = task::delete(&state.pool.try_get().await.unwrap(), task_id).await {
-        return Html::from(format!("<p>Задание удалено</p>")).into_response();
+    let db_client = state.pool.try_get().await.unwrap();
+    // rows go away with the task, files have to be removed by hand
+    let attachments = attachment::get_by_task(&db_client, task_id)
+        .await
+        .log_err()
+        .unwrap_or_default();
+    let before = task::get(&db_client, task_id).await.log_err().ok();
+    if task::delete(&db_client, task_id).await.log_err().is_ok() {
+        for a in attachments {
+            let _ = state.storage.delete(&a.storage_key).await.log_err();
+        }
+        audit
+            .record(
+                &db_client,
+                Some(&u),
+                Action::TaskDeleted,
+                Target::Task(task_id),
+                before.and_then(|t| serde_json::to_value(t).ok()),
+                None,
+            )
+            .await;
+        return Html::from("<p>Задание удалено</p>").into_response();
     }
 
-    return Html::from("<p>Неожиданная ошибка судьбы</p>").into_response();
+    Html::from("<p>Неожиданная ошибка судьбы</p>").into_response()
 }
 
 async fn assign_to(
     session: AuthSession,
+    audit: Audit,
     Path(task_id): Path<i32>,
     State(state): State<AppState>,
 ) -> impl IntoResponse {
     let u = session.user.unwrap();
     let db_client = state.pool.try_get().await.unwrap();
 
-    if let Ok(v) = task::assigned_to(&db_client, task_id).await {
-        match v {
-            Some(v) => {
-                if v != u.id {
-                    return Html::from("<p>Вы не можете взять чужое задание</p>").into_response();
-                }
-            }
-            None => (),
+    if let Ok(v) = task::assigned_to(&db_client, task_id).await.log_err() {
+        if v.is_some_and(|v| v != u.id) {
+            return Html::from("<p>Вы не можете взять чужое задание</p>").into_response();
+        }
+        if dependency::is_blocked(&db_client, task_id)
+            .await
+            .log_err()
+            .unwrap_or(true)
+        {
+            return Html::from("<p>Задание заблокировано другими заданиями</p>").into_response();
+        }
+        if task::assign_to(&db_client, task_id, Some(u.id))
+            .await
+            .log_err()
+            .is_ok_and(|n| n > 0)
+            && v.is_none()
+        {
+            state.metrics.record(TaskEvent::Claimed);
+            audit
+                .record(
+                    &db_client,
+                    Some(&u),
+                    Action::TaskAssigned,
+                    Target::Task(task_id),
+                    Some(json!({ "assigned_to": null })),
+                    Some(json!({ "assigned_to": u.id })),
+                )
+                .await;
+        }
+        if let Ok(true) = recommendation::log_accepted(&db_client, u.id, task_id)
+            .await
+            .log_err()
+        {
+            tracing::info!(user_id = u.id, task_id, "recommended quest taken");
         }
-        let _ = task::assign_to(&db_client, task_id, Some(u.id)).await;
         return Html::from(format!("
                 <button class='rpgui-button' type='button' hx-patch='/api/task/manage/complete/{task_id}' hx-target='closest div' onclick='setTaskInactive(this)'><p>Завершить</p></button>
-                <button class='rpgui-button' type='button' hx-patch='/api/task/manage/resign/{task_id}' hx-target='previous button' hx-swap='outerHTML' hx-on::before-request='this.remove()' onclick='setTaskInactive(this)'><p>Отказаться</p></button>"
+                <button class='rpgui-button' type='button' hx-patch='/api/task/manage/resign/{task_id}' hx-target='previous button' hx-swap='outerHTML' hx-on::before-request='this.remove()' onclick='setTaskInactive(this)'><p>Отказаться</p></button>
+                {}",
+                timer_button(task_id, false)
                 ))
             .into_response();
     }
 
-    return Html::from("<p>Неожиданная ошибка судьбы</p>").into_response();
+    Html::from("<p>Неожиданная ошибка судьбы</p>").into_response()
 }
 
 async fn resign(
     session: AuthSession,
+    audit: Audit,
     Path(task_id): Path<i32>,
     State(state): State<AppState>,
 ) -> impl IntoResponse {
     let u = session.user.unwrap();
     let db_client = state.pool.try_get().await.unwrap();
 
-    if let Ok(v) = task::assigned_to(&db_client, task_id).await {
-        match v {
-            Some(v) => {
-                if v != u.id {
-                    return Html::from("<p>Вы не можете отказаться от чужого задания</p>")
-                        .into_response();
-                }
-            }
-            None => (),
+    if let Ok(v) = task::assigned_to(&db_client, task_id).await.log_err() {
+        if v.is_some_and(|v| v != u.id) {
+            return Html::from("<p>Вы не можете отказаться от чужого задания</p>").into_response();
+        }
+        if task::assign_to(&db_client, task_id, None)
+            .await
+            .log_err()
+            .is_ok_and(|n| n > 0)
+            && v.is_some()
+        {
+            audit
+                .record(
+                    &db_client,
+                    Some(&u),
+                    Action::TaskResigned,
+                    Target::Task(task_id),
+                    Some(json!({ "assigned_to": v })),
+                    Some(json!({ "assigned_to": null })),
+                )
+                .await;
         }
-        let _ = task::assign_to(&db_client, task_id, None).await;
         return Html::from(format!(
             "<button class='rpgui-button' type='button' hx-patch='/api/task/manage/assign/{task_id}' hx-target='this' hx-swap='outerHTML' onclick='setTaskActive(this)'><p>Принять</p></button>"
         ))
         .into_response();
     }
 
-    return Html::from("<p>Неожиданная ошибка судьбы</p>").into_response();
+    Html::from("<p>Неожиданная ошибка судьбы</p>").into_response()
 }
 
 async fn complete(
     session: AuthSession,
+    audit: Audit,
+    Path(task_id): Path<i32>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let u = session.user.unwrap();
+    let db_client = state.pool.try_get().await.unwrap();
+
+    let Ok(assigned_to) = task::assigned_to(&db_client, task_id).await.log_err() else {
+        return Html::from("<p>Неожиданная ошибка судьбы</p>").into_response();
+    };
+    let blocked = dependency::is_blocked(&db_client, task_id)
+        .await
+        .log_err()
+        .unwrap_or(true);
+    let unfinished_children = dependency::has_unfinished_children(&db_client, task_id)
+        .await
+        .log_err()
+        .unwrap_or(true);
+    if let Some(message) = completion_refusal(u.id, assigned_to, blocked, unfinished_children) {
+        return Html::from(message).into_response();
+    }
+
+    match task::complete(&db_client, task_id, u.clone(), &state.config.rules)
+        .await
+        .log_err()
+    {
+        Ok(true) => {
+            state.metrics.record(TaskEvent::Completed);
+            audit
+                .record(
+                    &db_client,
+                    Some(&u),
+                    Action::TaskCompleted,
+                    Target::Task(task_id),
+                    None,
+                    Some(json!({ "completed_by": u.id })),
+                )
+                .await;
+            Html::from(format!("<p>Вы завершили заказ под номером {task_id}</p>")).into_response()
+        }
+        Ok(false) => Html::from("<p>Задание заблокировано или не все подзадания завершены</p>")
+            .into_response(),
+        Err(_) => Html::from("<p>Неожиданная ошибка судьбы</p>").into_response(),
+    }
+}
+
+// an unassigned quest can be completed by anyone, but not past its blockers and subquests
+fn completion_refusal(
+    user_id: i32,
+    assigned_to: Option<i32>,
+    blocked: bool,
+    unfinished_children: bool,
+) -> Option<&'static str> {
+    if assigned_to.is_some_and(|a| a != user_id) {
+        Some("<p>Вы не можете завершить чужое задание</p>")
+    } else if blocked {
+        Some("<p>Задание заблокировано другими заданиями</p>")
+    } else if unfinished_children {
+        Some("<p>Сначала завершите все подзадания</p>")
+    } else {
+        None
+    }
+}
+
+fn timer_button(task_id: i32, running: bool) -> String {
+    if running {
+        format!("<button class='rpgui-button' type='button' hx-patch='/api/task/manage/timer/pause/{task_id}' hx-swap='outerHTML'><p>Пауза</p></button>")
+    } else {
+        format!("<button class='rpgui-button' type='button' hx-patch='/api/task/manage/timer/start/{task_id}' hx-swap='outerHTML'><p>Начать отсчет</p></button>")
+    }
+}
+
+async fn start_timer(
+    session: AuthSession,
+    audit: Audit,
+    Path(task_id): Path<i32>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let u = session.user.unwrap();
+    let db_client = state.pool.try_get().await.unwrap();
+
+    if let Ok(t) = task::get(&db_client, task_id).await.log_err() {
+        if t.assigned_to != Some(u.id) {
+            return Html::from("<p>Вы не можете вести отсчет чужого задания</p>").into_response();
+        }
+        // the assignee stays on a completed quest, its time is settled
+        if t.is_completed {
+            return Html::from("<p>Задание уже завершено</p>").into_response();
+        }
+        if worklog::start(&db_client, task_id, u.id)
+            .await
+            .log_err()
+            .is_ok()
+        {
+            audit
+                .record(
+                    &db_client,
+                    Some(&u),
+                    Action::TimerStarted,
+                    Target::Task(task_id),
+                    None,
+                    None,
+                )
+                .await;
+            return Html::from(timer_button(task_id, true)).into_response();
+        }
+    }
+
+    Html::from("<p>Неожиданная ошибка судьбы</p>").into_response()
+}
+
+async fn pause_timer(
+    session: AuthSession,
+    audit: Audit,
+    Path(task_id): Path<i32>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let u = session.user.unwrap();
+    let db_client = state.pool.try_get().await.unwrap();
+
+    if let Ok(v) = task::assigned_to(&db_client, task_id).await.log_err() {
+        if v != Some(u.id) {
+            return Html::from("<p>Вы не можете вести отсчет чужого задания</p>").into_response();
+        }
+        if worklog::pause(&db_client, task_id, u.id)
+            .await
+            .log_err()
+            .is_ok()
+        {
+            audit
+                .record(
+                    &db_client,
+                    Some(&u),
+                    Action::TimerPaused,
+                    Target::Task(task_id),
+                    None,
+                    None,
+                )
+                .await;
+            return Html::from(timer_button(task_id, false)).into_response();
+        }
+    }
+
+    Html::from("<p>Неожиданная ошибка судьбы</p>").into_response()
+}
+
+#[derive(Deserialize)]
+struct WorklogForm {
+    hours: Box<str>,
+    note: Option<Box<str>>,
+}
+
+async fn add_worklog(
+    session: AuthSession,
+    audit: Audit,
     Path(task_id): Path<i32>,
     State(state): State<AppState>,
+    Form(payload): Form<WorklogForm>,
 ) -> impl IntoResponse {
     let u = session.user.unwrap();
     let db_client = state.pool.try_get().await.unwrap();
 
-    if let Ok(v) = task::assigned_to(&db_client, task_id).await {
-        match v {
-            Some(v) => {
-                if v != u.id {
-                    return Html::from("<p>Вы не можете завершить чужое задание</p>")
-                        .into_response();
-                }
+    // "1,5" is how hours are written here
+    let Some(hours) = payload
+        .hours
+        .trim()
+        .replace(',', ".")
+        .parse::<f32>()
+        .ok()
+        .filter(|h| *h > 0.0 && *h <= 24.0)
+    else {
+        return Html::from("<p>Время должно быть от 0 до 24 часов</p>").into_response();
+    };
+
+    if let Ok(t) = task::get(&db_client, task_id).await.log_err() {
+        if t.assigned_to != Some(u.id) {
+            return Html::from("<p>Вы не можете записать время чужого задания</p>").into_response();
+        }
+        // as with the timer, a completed quest takes no more time
+        if t.is_completed {
+            return Html::from("<p>Задание уже завершено</p>").into_response();
+        }
+        let note = payload.note.as_deref().filter(|n| !n.is_empty());
+        if worklog::add(&db_client, task_id, u.id, hours, note)
+            .await
+            .log_err()
+            .is_ok()
+        {
+            audit
+                .record(
+                    &db_client,
+                    Some(&u),
+                    Action::WorklogAdded,
+                    Target::Task(task_id),
+                    None,
+                    Some(json!({ "hours": hours, "note": note })),
+                )
+                .await;
+            if let Ok(spent) = worklog::get_spent(&db_client, task_id).await.log_err() {
+                return Html::from(format!("{spent:.1}")).into_response();
             }
-            None => (),
         }
-        let _ = task::complete(&db_client, task_id, u).await;
-        return Html::from(format!("<p>Вы завершили заказ под номером {task_id}</p>"))
+    }
+
+    Html::from("<p>Неожиданная ошибка судьбы</p>").into_response()
+}
+
+#[derive(Deserialize)]
+struct CampaignForm {
+    campaign_id: Option<Box<str>>,
+}
+
+async fn set_campaign(
+    session: AuthSession,
+    audit: Audit,
+    Path(task_id): Path<i32>,
+    State(state): State<AppState>,
+    Form(payload): Form<CampaignForm>,
+) -> impl IntoResponse {
+    let u = session.user.unwrap();
+
+    if !u.is_admin {
+        return Html::from("<p>Недостаточно прав для совершения заклинания</p>").into_response();
+    }
+
+    // an empty value removes the task from its campaign
+    let Ok(campaign_id) = optional_id(payload.campaign_id.as_deref()) else {
+        return Html::from("<p>Неверный номер кампании</p>").into_response();
+    };
+    let db_client = state.pool.try_get().await.unwrap();
+    let before = task::get(&db_client, task_id).await.log_err().ok();
+    if task::set_campaign(&db_client, task_id, campaign_id)
+        .await
+        .log_err()
+        .is_ok()
+    {
+        audit
+            .record(
+                &db_client,
+                Some(&u),
+                Action::CampaignSet,
+                Target::Task(task_id),
+                before.map(|t| json!({ "campaign_id": t.campaign_id })),
+                Some(json!({ "campaign_id": campaign_id })),
+            )
+            .await;
+        return Html::from("<p>Кампания задания изменена</p>").into_response();
+    }
+
+    Html::from("<p>Неожиданная ошибка судьбы</p>").into_response()
+}
+
+#[derive(Deserialize)]
+struct ParentForm {
+    parent_id: Option<Box<str>>,
+}
+
+async fn set_parent(
+    session: AuthSession,
+    audit: Audit,
+    Path(task_id): Path<i32>,
+    State(state): State<AppState>,
+    Form(payload): Form<ParentForm>,
+) -> impl IntoResponse {
+    let u = session.user.unwrap();
+    let mut db_client = state.pool.try_get().await.unwrap();
+
+    if !u.is_admin {
+        return Html::from("<p>Недостаточно прав для совершения заклинания</p>").into_response();
+    }
+
+    // an empty value detaches the sub-quest
+    let Ok(parent_id) = optional_id(payload.parent_id.as_deref()) else {
+        return Html::from("<p>Неверный номер родительского задания</p>").into_response();
+    };
+    let before = task::get(&db_client, task_id).await.log_err().ok();
+    match dependency::set_parent(&mut db_client, task_id, parent_id)
+        .await
+        .log_err()
+    {
+        Ok(true) => {
+            audit
+                .record(
+                    &db_client,
+                    Some(&u),
+                    Action::ParentSet,
+                    Target::Task(task_id),
+                    before.map(|t| json!({ "parent_id": t.parent_id })),
+                    Some(json!({ "parent_id": parent_id })),
+                )
+                .await;
+            Html::from("<p>Надзадание изменено</p>").into_response()
+        }
+        Ok(false) => Html::from("<p>Цепочка заданий не может замыкаться</p>").into_response(),
+        Err(_) => Html::from("<p>Неожиданная ошибка судьбы</p>").into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct DependencyForm {
+    blocked_by: i32,
+}
+
+async fn add_dependency(
+    session: AuthSession,
+    audit: Audit,
+    Path(task_id): Path<i32>,
+    State(state): State<AppState>,
+    Form(payload): Form<DependencyForm>,
+) -> impl IntoResponse {
+    let u = session.user.unwrap();
+    let mut db_client = state.pool.try_get().await.unwrap();
+
+    if !u.is_admin {
+        return Html::from("<p>Недостаточно прав для совершения заклинания</p>").into_response();
+    }
+
+    match dependency::add(&mut db_client, task_id, payload.blocked_by)
+        .await
+        .log_err()
+    {
+        Ok(true) => {
+            audit
+                .record(
+                    &db_client,
+                    Some(&u),
+                    Action::DependencyAdded,
+                    Target::Task(task_id),
+                    None,
+                    Some(json!({ "blocked_by": payload.blocked_by })),
+                )
+                .await;
+            Html::from(format!(
+                "<p>Задание заблокировано заданием №{}</p>",
+                payload.blocked_by
+            ))
+            .into_response()
+        }
+        Ok(false) => Html::from("<p>Цепочка заданий не может замыкаться</p>").into_response(),
+        Err(_) => Html::from("<p>Неожиданная ошибка судьбы</p>").into_response(),
+    }
+}
+
+async fn remove_dependency(
+    session: AuthSession,
+    audit: Audit,
+    Path((task_id, blocked_by)): Path<(i32, i32)>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let u = session.user.unwrap();
+
+    if !u.is_admin {
+        return Html::from("<p>Недостаточно прав для совершения заклинания</p>").into_response();
+    }
+
+    let db_client = state.pool.try_get().await.unwrap();
+    if dependency::remove(&db_client, task_id, blocked_by)
+        .await
+        .log_err()
+        .is_ok()
+    {
+        audit
+            .record(
+                &db_client,
+                Some(&u),
+                Action::DependencyRemoved,
+                Target::Task(task_id),
+                Some(json!({ "blocked_by": blocked_by })),
+                None,
+            )
+            .await;
+        return Html::from(format!("<p>Задание №{blocked_by} больше не блокирует</p>"))
             .into_response();
     }
 
-    return Html::from("<p>Неожиданная ошибка судьбы</p>").into_response();
+    Html::from("<p>Неожиданная ошибка судьбы</p>").into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocked_quests_are_not_completed() {
+        // unassigned, so only the blockers stand in the way
+        assert_eq!(
+            completion_refusal(1, None, true, false),
+            Some("<p>Задание заблокировано другими заданиями</p>")
+        );
+        assert_eq!(
+            completion_refusal(1, Some(1), true, false),
+            Some("<p>Задание заблокировано другими заданиями</p>")
+        );
+    }
+
+    #[test]
+    fn optional_ids() {
+        assert_eq!(optional_id(None), Ok(None));
+        assert_eq!(optional_id(Some("")), Ok(None));
+        assert_eq!(optional_id(Some("  ")), Ok(None));
+        assert_eq!(optional_id(Some(" 42 ")), Ok(Some(42)));
+        assert!(optional_id(Some("42a")).is_err());
+        assert!(optional_id(Some("№42")).is_err());
+    }
+
+    #[test]
+    fn completion_rules() {
+        assert_eq!(completion_refusal(1, None, false, false), None);
+        assert_eq!(completion_refusal(1, Some(1), false, false), None);
+        assert_eq!(
+            completion_refusal(1, Some(2), false, false),
+            Some("<p>Вы не можете завершить чужое задание</p>")
+        );
+        assert_eq!(
+            completion_refusal(1, Some(1), false, true),
+            Some("<p>Сначала завершите все подзадания</p>")
+        );
+    }
 }
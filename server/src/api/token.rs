@@ -1,7 +1,14 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::post, Json, Router};
 use serde::Serialize;
 
-use crate::{entities::invite, libs::auth::AuthSession, AppState};
+use crate::{
+    entities::{
+        audit::{Action, Target},
+        invite,
+    },
+    libs::{audit::Audit, auth::AuthSession, logging::LogErr},
+    AppState,
+};
 
 pub fn router() -> Router<AppState> {
     Router::new().route("/", post(create))
@@ -12,11 +19,26 @@ struct TokenData {
     token: Box<str>,
 }
 
-async fn create(auth_session: AuthSession, State(state): State<AppState>) -> impl IntoResponse {
+async fn create(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    audit: Audit,
+) -> impl IntoResponse {
     let u = auth_session.user.unwrap();
 
     if u.is_admin {
-        if let Ok(token) = invite::create(&state.pool.try_get().await.unwrap()).await {
+        let db_client = state.pool.try_get().await.unwrap();
+        if let Ok(token) = invite::create(&db_client).await.log_err() {
+            audit
+                .record(
+                    &db_client,
+                    Some(&u),
+                    Action::InviteCreated,
+                    Target::Invite,
+                    None,
+                    None,
+                )
+                .await;
             return Json::from(TokenData { token }).into_response();
         }
     }
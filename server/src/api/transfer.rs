@@ -0,0 +1,307 @@
+use axum::{
+    extract::{Multipart, Path, Query, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use tera::Context;
+
+use crate::{
+    entities::{
+        audit::{Action, Target},
+        task::Task,
+        transfer::{self, Completion, Member},
+    },
+    libs::{audit::Audit, auth::AuthSession, csv, logging::LogErr, metrics::TaskEvent},
+    AppState,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/import", post(import))
+        .route("/export/:table", get(export))
+}
+
+#[derive(Deserialize)]
+struct ImportQuery {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+fn report(state: &AppState, ctx: &Context) -> Response {
+    Html::from(state.template.render("importReport.html", ctx).unwrap()).into_response()
+}
+
+/// Quests from a CSV or JSON file. Nothing is created while any row is invalid,
+/// a dry run shows what would be created without keeping it.
+async fn import(
+    session: AuthSession,
+    State(state): State<AppState>,
+    audit: Audit,
+    Query(query): Query<ImportQuery>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let Some(u) = session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    if !u.is_admin {
+        return Html::from("<p>Недостаточно прав для совершения заклинания</p>").into_response();
+    }
+
+    let mut ctx = Context::new();
+    ctx.insert("dry_run", &query.dry_run);
+
+    let field = loop {
+        match multipart.next_field().await.log_err() {
+            Ok(Some(field)) if field.name() == Some("file") => break field,
+            Ok(Some(_)) => continue,
+            Ok(None) => return Html::from("<p>Свиток не приложен</p>").into_response(),
+            Err(_) => return Html::from("<p>Свиток слишком тяжел</p>").into_response(),
+        }
+    };
+    let Ok(text) = field.text().await.log_err() else {
+        return Html::from("<p>Свиток слишком тяжел</p>").into_response();
+    };
+    let parsed = match transfer::parse(&text) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            ctx.insert("error", message);
+            return report(&state, &ctx);
+        }
+    };
+    ctx.insert("errors", &parsed.errors);
+    if !parsed.errors.is_empty() && !query.dry_run {
+        return report(&state, &ctx);
+    }
+
+    let mut db_client = state.pool.try_get().await.unwrap();
+    if query.dry_run {
+        let Ok(quests) = transfer::preview(&db_client, parsed.quests).await.log_err() else {
+            return Html::from("<p>Неожиданная ошибка судьбы</p>").into_response();
+        };
+        ctx.insert("tasks", &quests);
+        return report(&state, &ctx);
+    }
+
+    let Ok(tasks) = transfer::import(&mut db_client, &parsed.quests)
+        .await
+        .log_err()
+    else {
+        return Html::from("<p>Неожиданная ошибка судьбы</p>").into_response();
+    };
+    for task in &tasks {
+        state.metrics.record(TaskEvent::Created);
+        audit
+            .record(
+                &db_client,
+                Some(&u),
+                Action::TaskCreated,
+                Target::Task(task.id),
+                None,
+                serde_json::to_value(task).ok(),
+            )
+            .await;
+    }
+    ctx.insert("tasks", &tasks);
+
+    report(&state, &ctx)
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum Table {
+    Tasks,
+    Completions,
+    Users,
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum Format {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    #[serde(default)]
+    format: Format,
+}
+
+const TASK_COLUMNS: [&str; 14] = [
+    "id",
+    "description",
+    "tags",
+    "complexity",
+    "expected_time",
+    "spent_time",
+    "assigned_to",
+    "due_at",
+    "campaign_id",
+    "parent_id",
+    "is_completed",
+    "created_at",
+    "updated_at",
+    "completed_at",
+];
+
+const COMPLETION_COLUMNS: [&str; 6] = ["id", "task_id", "user_id", "login", "xp", "completed_at"];
+
+const USER_COLUMNS: [&str; 9] = [
+    "id",
+    "login",
+    "name",
+    "class",
+    "is_admin",
+    "completed",
+    "xp",
+    "created_at",
+    "updated_at",
+];
+
+fn opt<T: ToString>(v: Option<T>) -> String {
+    v.map(|v| v.to_string()).unwrap_or_default()
+}
+
+// the first columns are the ones import reads, an export can be imported back
+fn tasks_to_csv(tasks: &[Task]) -> String {
+    let mut out = String::new();
+    csv::write_record(&mut out, TASK_COLUMNS);
+    for t in tasks {
+        csv::write_record(
+            &mut out,
+            [
+                t.id.to_string().as_str(),
+                &t.description,
+                &t.tags.join(" "),
+                &t.complexity.to_string(),
+                &t.expected_time.to_string(),
+                &t.spent_time.to_string(),
+                &opt(t.assigned_to),
+                &opt(t.due_at.map(|d| d.to_rfc3339())),
+                &opt(t.campaign_id),
+                &opt(t.parent_id),
+                &t.is_completed.to_string(),
+                &t.created_at.to_rfc3339(),
+                &t.updated_at.to_rfc3339(),
+                &opt(t.completed_at.map(|d| d.to_rfc3339())),
+            ],
+        );
+    }
+    out
+}
+
+fn completions_to_csv(completions: &[Completion]) -> String {
+    let mut out = String::new();
+    csv::write_record(&mut out, COMPLETION_COLUMNS);
+    for c in completions {
+        csv::write_record(
+            &mut out,
+            [
+                c.id.to_string().as_str(),
+                &c.task_id.to_string(),
+                &opt(c.user_id),
+                c.login.as_deref().unwrap_or_default(),
+                &opt(c.xp),
+                &opt(c.completed_at.map(|d| d.to_rfc3339())),
+            ],
+        );
+    }
+    out
+}
+
+fn users_to_csv(users: &[Member]) -> String {
+    let mut out = String::new();
+    csv::write_record(&mut out, USER_COLUMNS);
+    for m in users {
+        csv::write_record(
+            &mut out,
+            [
+                m.id.to_string().as_str(),
+                &m.login,
+                &m.name,
+                &m.class.to_string(),
+                &m.is_admin.to_string(),
+                &m.completed.to_string(),
+                &m.xp.to_string(),
+                &m.created_at.to_rfc3339(),
+                &m.updated_at.to_rfc3339(),
+            ],
+        );
+    }
+    out
+}
+
+fn export_response<T: Serialize>(
+    name: &str,
+    format: Format,
+    rows: Result<Vec<T>, tokio_postgres::Error>,
+    to_csv: fn(&[T]) -> String,
+) -> Response {
+    let Ok(rows) = rows.log_err() else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    match format {
+        Format::Json => (
+            [(
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{name}.json\""),
+            )],
+            Json::from(rows),
+        )
+            .into_response(),
+        Format::Csv => (
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_owned()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{name}.csv\""),
+                ),
+            ],
+            to_csv(&rows),
+        )
+            .into_response(),
+    }
+}
+
+/// A whole table for backups and reports elsewhere, users come without password hashes.
+async fn export(
+    session: AuthSession,
+    State(state): State<AppState>,
+    Path(table): Path<Table>,
+    Query(query): Query<ExportQuery>,
+) -> impl IntoResponse {
+    let Some(u) = session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    if !u.is_admin {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let db_client = state.pool.try_get().await.unwrap();
+    match table {
+        Table::Tasks => export_response(
+            "tasks",
+            query.format,
+            transfer::get_tasks(&db_client).await,
+            tasks_to_csv,
+        ),
+        Table::Completions => export_response(
+            "completions",
+            query.format,
+            transfer::get_completions(&db_client).await,
+            completions_to_csv,
+        ),
+        Table::Users => export_response(
+            "users",
+            query.format,
+            transfer::get_members(&db_client).await,
+            users_to_csv,
+        ),
+    }
+}
@@ -0,0 +1,368 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Row;
+
+use super::{page::empty_as_none, user::Class};
+use crate::libs::db::DbClient;
+use tracing::instrument;
+
+const TOP_TAGS: i64 = 12;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Period {
+    Week,
+    #[default]
+    Month,
+    Quarter,
+    Year,
+}
+
+impl Period {
+    /// Width of one bar on the charts.
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Period::Week | Period::Month => "day",
+            Period::Quarter => "week",
+            Period::Year => "month",
+        }
+    }
+
+    fn span(&self) -> &'static str {
+        match self {
+            Period::Week => "6 days",
+            Period::Month => "29 days",
+            Period::Quarter => "12 weeks",
+            Period::Year => "11 months",
+        }
+    }
+
+    // start of the first bucket
+    fn since(&self) -> String {
+        format!(
+            "date_trunc('{}', now() - interval '{}')",
+            self.unit(),
+            self.span()
+        )
+    }
+
+    fn buckets(&self) -> String {
+        format!(
+            "generate_series({}, date_trunc('{unit}', now()), interval '1 {unit}') AS b(bucket)",
+            self.since(),
+            unit = self.unit()
+        )
+    }
+}
+
+/// There are no guilds in the tree, a team is cut out by the tag subtree it works on
+/// and the class of its adventurers, as on the leaderboard.
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct Filter {
+    #[serde(default)]
+    pub period: Period,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub tag: Option<String>,
+    /// Narrows down who did the work, tasks created are counted for everyone.
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub class: Option<Class>,
+}
+
+impl Filter {
+    fn class(&self) -> Option<i16> {
+        self.class.map(|c| c.into())
+    }
+}
+
+const TASK_SCOPE: &str = "($1::text IS NULL OR t.tags && tag_subtree($1))";
+const CLASS_SCOPE: &str = "($2::smallint IS NULL OR u.class = $2)";
+
+#[derive(Serialize)]
+pub struct Throughput {
+    pub bucket: DateTime<Utc>,
+    pub created: i64,
+    pub completed: i64,
+}
+
+impl From<Row> for Throughput {
+    fn from(row: Row) -> Self {
+        Throughput {
+            bucket: row.get("bucket"),
+            created: row.get("created"),
+            completed: row.get("completed"),
+        }
+    }
+}
+
+/// Hours from the claim to the completion, of the tasks completed in the bucket.
+#[derive(Serialize)]
+pub struct CycleTime {
+    pub bucket: DateTime<Utc>,
+    pub completed: i64,
+    pub avg_hours: Option<f64>,
+    pub median_hours: Option<f64>,
+    pub p90_hours: Option<f64>,
+}
+
+impl From<Row> for CycleTime {
+    fn from(row: Row) -> Self {
+        CycleTime {
+            bucket: row.get("bucket"),
+            completed: row.get("completed"),
+            avg_hours: row.get("avg_hours"),
+            median_hours: row.get("median_hours"),
+            p90_hours: row.get("p90_hours"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Band {
+    pub label: &'static str,
+    pub count: i64,
+}
+
+/// Logged hours against the estimate of completed tasks, tasks without a worklog are left out.
+#[derive(Serialize)]
+pub struct Estimates {
+    pub tasks: i64,
+    pub mean_ratio: Option<f64>,
+    pub median_ratio: Option<f64>,
+    pub bands: Vec<Band>,
+}
+
+const RATIO_BANDS: [(&str, &str); 5] = [
+    ("< 0.5", "ratio < 0.5"),
+    ("0.5-0.8", "ratio >= 0.5 AND ratio < 0.8"),
+    ("0.8-1.25", "ratio >= 0.8 AND ratio <= 1.25"),
+    ("1.25-2", "ratio > 1.25 AND ratio <= 2"),
+    ("> 2", "ratio > 2"),
+];
+
+#[derive(Serialize)]
+pub struct Workload {
+    pub user_id: i32,
+    pub login: Box<str>,
+    pub class: Class,
+    /// Claimed and not completed, right now.
+    pub active: i64,
+    pub completed: i64,
+    pub hours: f64,
+}
+
+impl From<Row> for Workload {
+    fn from(row: Row) -> Self {
+        Workload {
+            user_id: row.get("id"),
+            login: row.get("login"),
+            class: row.get::<&str, i16>("class").into(),
+            active: row.get("active"),
+            completed: row.get("completed"),
+            hours: row.get("hours"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Share {
+    pub name: Box<str>,
+    pub count: i64,
+}
+
+/// Completed tasks by tag and by complexity.
+#[derive(Serialize)]
+pub struct Distribution {
+    pub tags: Vec<Share>,
+    pub complexity: Vec<Share>,
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_throughput(
+    db_client: &DbClient<'_>,
+    filter: &Filter,
+) -> Result<Vec<Throughput>, tokio_postgres::Error> {
+    let unit = filter.period.unit();
+    Ok(db_client
+        .query(
+            &format!(
+                "SELECT b.bucket,
+                    (SELECT COUNT(*) FROM tasks t
+                        WHERE t.created_at >= b.bucket AND t.created_at < b.bucket + interval '1 {unit}'
+                        AND {TASK_SCOPE}) AS created,
+                    (SELECT COUNT(*) FROM completed_tasks ct JOIN tasks t ON t.id = ct.task_id JOIN users u ON u.id = ct.user_id
+                        WHERE ct.completed_at >= b.bucket AND ct.completed_at < b.bucket + interval '1 {unit}'
+                        AND {TASK_SCOPE} AND {CLASS_SCOPE}) AS completed
+                FROM {} ORDER BY b.bucket",
+                filter.period.buckets()
+            ),
+            &[&filter.tag, &filter.class()],
+        )
+        .await?
+        .into_iter()
+        .map(Throughput::from)
+        .collect())
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_cycle_time(
+    db_client: &DbClient<'_>,
+    filter: &Filter,
+) -> Result<Vec<CycleTime>, tokio_postgres::Error> {
+    Ok(db_client
+        .query(
+            &format!(
+                "WITH done AS (
+                    SELECT date_trunc('{unit}', ct.completed_at) AS bucket,
+                        EXTRACT(EPOCH FROM (ct.completed_at - t.assigned_at))::float8 / 3600 AS hours
+                    FROM completed_tasks ct JOIN tasks t ON t.id = ct.task_id JOIN users u ON u.id = ct.user_id
+                    WHERE ct.completed_at >= {since} AND t.assigned_at <= ct.completed_at
+                    AND {TASK_SCOPE} AND {CLASS_SCOPE}
+                )
+                SELECT b.bucket, COUNT(d.hours) AS completed, AVG(d.hours) AS avg_hours,
+                    percentile_cont(0.5) WITHIN GROUP (ORDER BY d.hours) AS median_hours,
+                    percentile_cont(0.9) WITHIN GROUP (ORDER BY d.hours) AS p90_hours
+                FROM {buckets} LEFT JOIN done d ON d.bucket = b.bucket
+                GROUP BY b.bucket ORDER BY b.bucket",
+                unit = filter.period.unit(),
+                since = filter.period.since(),
+                buckets = filter.period.buckets()
+            ),
+            &[&filter.tag, &filter.class()],
+        )
+        .await?
+        .into_iter()
+        .map(CycleTime::from)
+        .collect())
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_estimates(
+    db_client: &DbClient<'_>,
+    filter: &Filter,
+) -> Result<Estimates, tokio_postgres::Error> {
+    let bands = RATIO_BANDS
+        .iter()
+        .enumerate()
+        .map(|(i, (_, condition))| format!("COUNT(*) FILTER (WHERE {condition}) AS band_{i}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let row = db_client
+        .query_one(
+            &format!(
+                "WITH ratios AS (
+                    SELECT (w.spent / t.expected_time)::float8 AS ratio
+                    FROM completed_tasks ct JOIN tasks t ON t.id = ct.task_id JOIN users u ON u.id = ct.user_id
+                    JOIN (
+                        SELECT task_id, SUM(EXTRACT(EPOCH FROM (stopped_at - started_at))) / 3600 AS spent
+                        FROM worklogs WHERE stopped_at IS NOT NULL GROUP BY task_id
+                    ) w ON w.task_id = t.id
+                    WHERE ct.completed_at >= {since} AND w.spent > 0
+                    AND {TASK_SCOPE} AND {CLASS_SCOPE}
+                )
+                SELECT COUNT(*) AS tasks, AVG(ratio) AS mean_ratio,
+                    percentile_cont(0.5) WITHIN GROUP (ORDER BY ratio) AS median_ratio, {bands}
+                FROM ratios",
+                since = filter.period.since(),
+            ),
+            &[&filter.tag, &filter.class()],
+        )
+        .await?;
+
+    Ok(Estimates {
+        tasks: row.get("tasks"),
+        mean_ratio: row.get("mean_ratio"),
+        median_ratio: row.get("median_ratio"),
+        bands: RATIO_BANDS
+            .iter()
+            .enumerate()
+            .map(|(i, (label, _))| Band {
+                label,
+                count: row.get(format!("band_{i}").as_str()),
+            })
+            .collect(),
+    })
+}
+
+/// Adventurers with anything claimed, completed or logged in the period, the busiest first.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_workload(
+    db_client: &DbClient<'_>,
+    filter: &Filter,
+) -> Result<Vec<Workload>, tokio_postgres::Error> {
+    Ok(db_client
+        .query(
+            &format!(
+                "SELECT * FROM (
+                    SELECT u.id, u.login, u.class,
+                        (SELECT COUNT(*) FROM tasks t WHERE t.assigned_to = u.id
+                            AND NOT EXISTS (SELECT 1 FROM completed_tasks WHERE task_id = t.id)
+                            AND {TASK_SCOPE}) AS active,
+                        (SELECT COUNT(*) FROM completed_tasks ct JOIN tasks t ON t.id = ct.task_id
+                            WHERE ct.user_id = u.id AND ct.completed_at >= {since}
+                            AND {TASK_SCOPE}) AS completed,
+                        (SELECT COALESCE(SUM(EXTRACT(EPOCH FROM (COALESCE(w.stopped_at, now()) - GREATEST(w.started_at, {since})))), 0)::float8 / 3600
+                            FROM worklogs w JOIN tasks t ON t.id = w.task_id
+                            WHERE w.user_id = u.id AND COALESCE(w.stopped_at, now()) > {since}
+                            AND {TASK_SCOPE}) AS hours
+                    FROM users u WHERE {CLASS_SCOPE}
+                ) workload
+                WHERE active > 0 OR completed > 0 OR hours > 0
+                ORDER BY hours DESC, completed DESC, id",
+                since = filter.period.since(),
+            ),
+            &[&filter.tag, &filter.class()],
+        )
+        .await?
+        .into_iter()
+        .map(Workload::from)
+        .collect())
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_distribution(
+    db_client: &DbClient<'_>,
+    filter: &Filter,
+) -> Result<Distribution, tokio_postgres::Error> {
+    let since = filter.period.since();
+    let tags = db_client
+        .query(
+            &format!(
+                "SELECT tag AS name, COUNT(*) AS count
+                FROM completed_tasks ct JOIN tasks t ON t.id = ct.task_id JOIN users u ON u.id = ct.user_id,
+                    unnest(t.tags) AS tag
+                WHERE ct.completed_at >= {since} AND {TASK_SCOPE} AND {CLASS_SCOPE}
+                GROUP BY tag ORDER BY count DESC, tag LIMIT $3"
+            ),
+            &[&filter.tag, &filter.class(), &TOP_TAGS],
+        )
+        .await?
+        .into_iter()
+        .map(|row| Share {
+            name: row.get("name"),
+            count: row.get("count"),
+        })
+        .collect();
+    let complexity = db_client
+        .query(
+            &format!(
+                "SELECT c.complexity, COUNT(t.id) AS count
+                FROM generate_series(0, 2) AS c(complexity)
+                LEFT JOIN (completed_tasks ct JOIN tasks t ON t.id = ct.task_id JOIN users u ON u.id = ct.user_id)
+                    ON t.complexity = c.complexity AND ct.completed_at >= {since}
+                    AND {TASK_SCOPE} AND {CLASS_SCOPE}
+                GROUP BY c.complexity ORDER BY c.complexity"
+            ),
+            &[&filter.tag, &filter.class()],
+        )
+        .await?
+        .into_iter()
+        .map(|row| Share {
+            name: Class::from(row.get::<&str, i32>("complexity") as i16)
+                .to_string()
+                .into(),
+            count: row.get("count"),
+        })
+        .collect();
+
+    Ok(Distribution { tags, complexity })
+}
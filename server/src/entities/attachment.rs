@@ -0,0 +1,96 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio_postgres::Row;
+
+use crate::libs::db::DbClient;
+use tracing::instrument;
+
+#[derive(Serialize)]
+pub struct Attachment {
+    pub id: i32,
+    pub task_id: i32,
+    pub uploader_id: Option<i32>,
+    pub file_name: Box<str>,
+    pub mime: Box<str>,
+    pub size: i64,
+    #[serde(skip)]
+    pub storage_key: Box<str>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<Row> for Attachment {
+    fn from(row: Row) -> Self {
+        Attachment {
+            id: row.get("id"),
+            task_id: row.get("task_id"),
+            uploader_id: row.get("uploader_id"),
+            file_name: row.get("file_name"),
+            mime: row.get("mime"),
+            size: row.get("size"),
+            storage_key: row.get("storage_key"),
+            created_at: row.get("created_at"),
+        }
+    }
+}
+
+pub struct AttachmentCreateData<'a> {
+    pub task_id: i32,
+    pub uploader_id: i32,
+    pub file_name: &'a str,
+    pub mime: &'a str,
+    pub size: i64,
+    pub storage_key: &'a str,
+}
+
+#[instrument(level = "debug", skip(db_client, attachment))]
+pub async fn create(
+    db_client: &DbClient<'_>,
+    attachment: &AttachmentCreateData<'_>,
+) -> Result<Attachment, tokio_postgres::Error> {
+    Ok(db_client
+        .query_one(
+            "INSERT INTO attachments (task_id, uploader_id, file_name, mime, size, storage_key)
+                VALUES ($1, $2, $3, $4, $5, $6) RETURNING *",
+            &[
+                &attachment.task_id,
+                &attachment.uploader_id,
+                &attachment.file_name,
+                &attachment.mime,
+                &attachment.size,
+                &attachment.storage_key,
+            ],
+        )
+        .await?
+        .into())
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get(db_client: &DbClient<'_>, id: i32) -> Result<Attachment, tokio_postgres::Error> {
+    Ok(db_client
+        .query_one("SELECT * FROM attachments WHERE id = $1", &[&id])
+        .await?
+        .into())
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_by_task(
+    db_client: &DbClient<'_>,
+    task_id: i32,
+) -> Result<Vec<Attachment>, tokio_postgres::Error> {
+    Ok(db_client
+        .query(
+            "SELECT * FROM attachments WHERE task_id = $1 ORDER BY created_at, id",
+            &[&task_id],
+        )
+        .await?
+        .into_iter()
+        .map(Attachment::from)
+        .collect())
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn delete(db_client: &DbClient<'_>, id: i32) -> Result<u64, tokio_postgres::Error> {
+    db_client
+        .execute("DELETE FROM attachments WHERE id = $1", &[&id])
+        .await
+}
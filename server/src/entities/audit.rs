@@ -0,0 +1,301 @@
+use std::{fmt, net::IpAddr, str::FromStr};
+
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio_postgres::Row;
+
+use super::page::{empty_as_none, Cursor, Page};
+use crate::libs::db::DbClient;
+use tracing::instrument;
+
+const AUDIT_PAGE_SIZE: i64 = 50;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    TaskCreated,
+    TaskDeleted,
+    TaskAssigned,
+    TaskResigned,
+    TaskCompleted,
+    TimerStarted,
+    TimerPaused,
+    WorklogAdded,
+    CampaignSet,
+    ParentSet,
+    DependencyAdded,
+    DependencyRemoved,
+    InviteCreated,
+    SignedUp,
+    SignedIn,
+    SignInFailed,
+    SignedOut,
+}
+
+impl Action {
+    pub const ALL: [Action; 17] = [
+        Action::TaskCreated,
+        Action::TaskDeleted,
+        Action::TaskAssigned,
+        Action::TaskResigned,
+        Action::TaskCompleted,
+        Action::TimerStarted,
+        Action::TimerPaused,
+        Action::WorklogAdded,
+        Action::CampaignSet,
+        Action::ParentSet,
+        Action::DependencyAdded,
+        Action::DependencyRemoved,
+        Action::InviteCreated,
+        Action::SignedUp,
+        Action::SignedIn,
+        Action::SignInFailed,
+        Action::SignedOut,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::TaskCreated => "task_created",
+            Action::TaskDeleted => "task_deleted",
+            Action::TaskAssigned => "task_assigned",
+            Action::TaskResigned => "task_resigned",
+            Action::TaskCompleted => "task_completed",
+            Action::TimerStarted => "timer_started",
+            Action::TimerPaused => "timer_paused",
+            Action::WorklogAdded => "worklog_added",
+            Action::CampaignSet => "campaign_set",
+            Action::ParentSet => "parent_set",
+            Action::DependencyAdded => "dependency_added",
+            Action::DependencyRemoved => "dependency_removed",
+            Action::InviteCreated => "invite_created",
+            Action::SignedUp => "signed_up",
+            Action::SignedIn => "signed_in",
+            Action::SignInFailed => "sign_in_failed",
+            Action::SignedOut => "signed_out",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::TaskCreated => "Создание задания",
+            Action::TaskDeleted => "Удаление задания",
+            Action::TaskAssigned => "Принятие задания",
+            Action::TaskResigned => "Отказ от задания",
+            Action::TaskCompleted => "Завершение задания",
+            Action::TimerStarted => "Начало отсчета",
+            Action::TimerPaused => "Пауза отсчета",
+            Action::WorklogAdded => "Запись времени",
+            Action::CampaignSet => "Смена кампании",
+            Action::ParentSet => "Смена надзадания",
+            Action::DependencyAdded => "Блокировка заданием",
+            Action::DependencyRemoved => "Снятие блокировки",
+            Action::InviteCreated => "Создание приглашения",
+            Action::SignedUp => "Вступление в гильдию",
+            Action::SignedIn => "Вход",
+            Action::SignInFailed => "Неудачный вход",
+            Action::SignedOut => "Выход",
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Action::ALL.into_iter().find(|a| a.as_str() == s).ok_or(())
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What the action was done to. Invites have no id worth keeping, their token is a secret.
+#[derive(Debug, Clone, Copy)]
+pub enum Target {
+    Task(i32),
+    User(i32),
+    /// A user known only by the login that was tried, there may be no such one.
+    Login,
+    Invite,
+}
+
+impl Target {
+    fn kind(&self) -> &'static str {
+        match self {
+            Target::Task(_) => "task",
+            Target::User(_) | Target::Login => "user",
+            Target::Invite => "invite",
+        }
+    }
+
+    fn id(&self) -> Option<i32> {
+        match self {
+            Target::Task(id) | Target::User(id) => Some(*id),
+            Target::Login | Target::Invite => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct NewEntry<'a> {
+    pub actor_id: Option<i32>,
+    pub actor_login: Option<&'a str>,
+    pub action: Action,
+    pub target: Target,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub ip: Option<IpAddr>,
+}
+
+#[derive(Serialize)]
+pub struct Entry {
+    pub id: i32,
+    pub at: DateTime<Utc>,
+    pub actor_id: Option<i32>,
+    pub actor_login: Option<Box<str>>,
+    pub action: Box<str>,
+    pub target_type: Box<str>,
+    pub target_id: Option<i32>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub ip: Option<IpAddr>,
+}
+
+impl From<Row> for Entry {
+    fn from(row: Row) -> Self {
+        Entry {
+            id: row.get("id"),
+            at: row.get("at"),
+            actor_id: row.get("actor_id"),
+            actor_login: row.get("actor_login"),
+            action: row.get("action"),
+            target_type: row.get("target_type"),
+            target_id: row.get("target_id"),
+            before: row.get("before"),
+            after: row.get("after"),
+            ip: row.get("ip"),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct Filter {
+    /// Login of the actor at the time of the action.
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub actor: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub action: Option<Action>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub target_type: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub target_id: Option<i32>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub from: Option<NaiveDate>,
+    /// Inclusive, the whole day is taken.
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub to: Option<NaiveDate>,
+}
+
+impl Filter {
+    fn since(&self) -> Option<DateTime<Utc>> {
+        self.from
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .map(|d| d.and_utc())
+    }
+
+    fn until(&self) -> Option<DateTime<Utc>> {
+        self.to
+            .and_then(|d| d.checked_add_days(Days::new(1)))
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .map(|d| d.and_utc())
+    }
+}
+
+// newest first, the cursor is the id of the last row seen
+const FILTERED: &str = "SELECT * FROM audit_log
+    WHERE ($1::text IS NULL OR actor_login = $1) AND ($2::text IS NULL OR action = $2)
+    AND ($3::text IS NULL OR target_type = $3) AND ($4::int IS NULL OR target_id = $4)
+    AND ($5::timestamptz IS NULL OR at >= $5) AND ($6::timestamptz IS NULL OR at < $6)
+    AND ($7::int IS NULL OR id < $7)
+    ORDER BY id DESC";
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn create(
+    db_client: &DbClient<'_>,
+    entry: &NewEntry<'_>,
+) -> Result<u64, tokio_postgres::Error> {
+    db_client
+        .execute(
+            "INSERT INTO audit_log (actor_id, actor_login, action, target_type, target_id, before, after, ip)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+            &[
+                &entry.actor_id,
+                &entry.actor_login,
+                &entry.action.as_str(),
+                &entry.target.kind(),
+                &entry.target.id(),
+                &entry.before,
+                &entry.after,
+                &entry.ip,
+            ],
+        )
+        .await
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get(
+    db_client: &DbClient<'_>,
+    filter: &Filter,
+    cursor: &Cursor,
+) -> Result<Page<Entry>, tokio_postgres::Error> {
+    let limit = cursor.limit(AUDIT_PAGE_SIZE);
+    let entries = db_client
+        .query(
+            &format!("{FILTERED} LIMIT $8"),
+            &[
+                &filter.actor,
+                &filter.action.map(|a| a.as_str()),
+                &filter.target_type,
+                &filter.target_id,
+                &filter.since(),
+                &filter.until(),
+                &cursor.after,
+                &(limit + 1),
+            ],
+        )
+        .await?
+        .into_iter()
+        .map(Entry::from)
+        .collect();
+
+    Ok(Page::new(entries, limit, |e| e.id))
+}
+
+/// Every entry that matches the filter, for export.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_all(
+    db_client: &DbClient<'_>,
+    filter: &Filter,
+) -> Result<Vec<Entry>, tokio_postgres::Error> {
+    Ok(db_client
+        .query(
+            FILTERED,
+            &[
+                &filter.actor,
+                &filter.action.map(|a| a.as_str()),
+                &filter.target_type,
+                &filter.target_id,
+                &filter.since(),
+                &filter.until(),
+                &None::<i32>,
+            ],
+        )
+        .await?
+        .into_iter()
+        .map(Entry::from)
+        .collect())
+}
@@ -0,0 +1,278 @@
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::join;
+use tokio_postgres::Row;
+
+use super::{
+    campaign::{self, Campaign},
+    page::empty_as_none,
+};
+use crate::libs::db::DbClient;
+use tracing::instrument;
+
+const DEFAULT_PERIODS: i64 = 8;
+const MAX_PERIODS: i64 = 52;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Period {
+    #[default]
+    Week,
+    Month,
+}
+
+impl Period {
+    fn unit(&self) -> &'static str {
+        match self {
+            Period::Week => "week",
+            Period::Month => "month",
+        }
+    }
+
+    /// First and last day of the period the date falls in.
+    pub fn around(&self, date: NaiveDate) -> (NaiveDate, NaiveDate) {
+        match self {
+            Period::Week => {
+                let start = date - Days::new(date.weekday().num_days_from_monday().into());
+                (start, start + Days::new(6))
+            }
+            Period::Month => {
+                let start = date.with_day(1).unwrap_or(date);
+                (start, start + Months::new(1) - Days::new(1))
+            }
+        }
+    }
+}
+
+/// Tasks of a campaign, or every task over a calendar period when there is none.
+/// `expected_time` stands for story points.
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct Scope {
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub campaign_id: Option<i32>,
+    #[serde(default)]
+    pub period: Period,
+    /// How many periods, or campaigns, velocity looks back.
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub periods: Option<i64>,
+}
+
+impl Scope {
+    pub fn periods(&self) -> i64 {
+        self.periods
+            .unwrap_or(DEFAULT_PERIODS)
+            .clamp(1, MAX_PERIODS)
+    }
+}
+
+#[derive(Serialize)]
+pub struct Day {
+    pub day: NaiveDate,
+    /// Estimated hours of the tasks open at the end of the day.
+    pub remaining: f64,
+    /// Estimated hours of the tasks completed during the day.
+    pub completed: f64,
+}
+
+impl From<Row> for Day {
+    fn from(row: Row) -> Self {
+        Day {
+            day: row.get("day"),
+            remaining: row.get("remaining"),
+            completed: row.get("completed"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Burndown {
+    pub starts_on: NaiveDate,
+    pub ends_on: NaiveDate,
+    /// Up to today, the days ahead are not known yet.
+    pub days: Vec<Day>,
+}
+
+impl Burndown {
+    pub fn dates(&self) -> Vec<NaiveDate> {
+        self.starts_on
+            .iter_days()
+            .take_while(|d| *d <= self.ends_on)
+            .collect()
+    }
+
+    /// A straight line from the largest scope to nothing on the last day. Tasks are
+    /// often added after the start, so the first day alone may have no scope at all.
+    pub fn ideal(&self) -> Vec<f64> {
+        let start = self.days.iter().map(|d| d.remaining).fold(0.0, f64::max);
+        let total = self.dates().len();
+        (0..total)
+            .map(|i| match total {
+                1 => 0.0,
+                _ => start * (1.0 - i as f64 / (total - 1) as f64),
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+pub struct Velocity {
+    pub label: Box<str>,
+    pub starts_on: NaiveDate,
+    /// Estimated hours planned, known only for campaigns.
+    pub committed: Option<f64>,
+    /// Estimated hours completed within the period.
+    pub completed: f64,
+    pub tasks: i64,
+}
+
+#[derive(Serialize)]
+pub struct Report {
+    pub campaign: Option<Campaign>,
+    pub burndown: Burndown,
+    pub velocity: Vec<Velocity>,
+}
+
+/// Burndown over the campaign's dates or the current calendar period, and velocity before it.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_report(
+    db_client: &DbClient<'_>,
+    scope: &Scope,
+) -> Result<Report, tokio_postgres::Error> {
+    match scope.campaign_id {
+        Some(id) => {
+            let c = campaign::get(db_client, id).await?;
+            let (burndown, velocity) = join!(
+                get(db_client, c.starts_on, c.ends_on, Some(id)),
+                get_velocity_by_campaign(db_client, &c, scope.periods())
+            );
+            Ok(Report {
+                campaign: Some(c),
+                burndown: burndown?,
+                velocity: velocity?,
+            })
+        }
+        None => {
+            let (starts_on, ends_on) = scope.period.around(Utc::now().date_naive());
+            let (burndown, velocity) = join!(
+                get(db_client, starts_on, ends_on, None),
+                get_velocity_by_period(db_client, scope.period, scope.periods())
+            );
+            Ok(Report {
+                campaign: None,
+                burndown: burndown?,
+                velocity: velocity?,
+            })
+        }
+    }
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get(
+    db_client: &DbClient<'_>,
+    starts_on: NaiveDate,
+    ends_on: NaiveDate,
+    campaign_id: Option<i32>,
+) -> Result<Burndown, tokio_postgres::Error> {
+    // a task counts from its creation, a campaign gains the tasks added to it later as scope;
+    // a completion of unknown time is taken as done before the range
+    let days = db_client
+        .query(
+            "SELECT d.day::date AS day,
+                COALESCE((SELECT SUM(t.expected_time) FROM tasks t
+                    WHERE ($3::int IS NULL OR t.campaign_id = $3) AND t.created_at < d.day + interval '1 day'
+                    AND NOT EXISTS (SELECT 1 FROM completed_tasks ct
+                        WHERE ct.task_id = t.id AND COALESCE(ct.completed_at, '-infinity') < d.day + interval '1 day')), 0)::float8 AS remaining,
+                COALESCE((SELECT SUM(t.expected_time) FROM completed_tasks ct JOIN tasks t ON t.id = ct.task_id
+                    WHERE ($3::int IS NULL OR t.campaign_id = $3)
+                    AND ct.completed_at >= d.day AND ct.completed_at < d.day + interval '1 day'), 0)::float8 AS completed
+            FROM generate_series($1::date, LEAST($2::date, CURRENT_DATE), interval '1 day') AS d(day)
+            ORDER BY d.day",
+            &[&starts_on, &ends_on, &campaign_id],
+        )
+        .await?
+        .into_iter()
+        .map(Day::from)
+        .collect();
+
+    Ok(Burndown {
+        starts_on,
+        ends_on,
+        days,
+    })
+}
+
+/// The campaign and the ones before it, oldest first.
+#[instrument(level = "debug", skip(db_client, campaign))]
+pub async fn get_velocity_by_campaign(
+    db_client: &DbClient<'_>,
+    campaign: &Campaign,
+    periods: i64,
+) -> Result<Vec<Velocity>, tokio_postgres::Error> {
+    let mut velocity: Vec<_> = db_client
+        .query(
+            "SELECT c.name, c.starts_on,
+                COALESCE(SUM(t.expected_time), 0)::float8 AS committed,
+                COALESCE(SUM(t.expected_time) FILTER (WHERE COALESCE(ct.completed_at, '-infinity') < c.ends_on + 1), 0)::float8 AS completed,
+                COUNT(ct.id) FILTER (WHERE COALESCE(ct.completed_at, '-infinity') < c.ends_on + 1) AS tasks
+            FROM campaigns c
+            LEFT JOIN tasks t ON t.campaign_id = c.id
+            LEFT JOIN completed_tasks ct ON ct.task_id = t.id
+            WHERE (c.starts_on, c.id) <= ($1, $2)
+            GROUP BY c.id ORDER BY c.starts_on DESC, c.id DESC LIMIT $3",
+            &[&campaign.starts_on, &campaign.id, &periods],
+        )
+        .await?
+        .into_iter()
+        .map(|row| Velocity {
+            label: row.get("name"),
+            starts_on: row.get("starts_on"),
+            committed: row.get("committed"),
+            completed: row.get("completed"),
+            tasks: row.get("tasks"),
+        })
+        .collect();
+    velocity.reverse();
+
+    Ok(velocity)
+}
+
+/// The current period and the ones before it, oldest first.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_velocity_by_period(
+    db_client: &DbClient<'_>,
+    period: Period,
+    periods: i64,
+) -> Result<Vec<Velocity>, tokio_postgres::Error> {
+    let label_format = match period {
+        Period::Week => "%d.%m",
+        Period::Month => "%m.%Y",
+    };
+    Ok(db_client
+        .query(
+            &format!(
+                "SELECT b.bucket,
+                    COALESCE(SUM(t.expected_time), 0)::float8 AS completed, COUNT(ct.id) AS tasks
+                FROM generate_series(date_trunc('{unit}', now()) - ($1::int8 - 1) * interval '1 {unit}',
+                    date_trunc('{unit}', now()), interval '1 {unit}') AS b(bucket)
+                LEFT JOIN completed_tasks ct
+                    ON ct.completed_at >= b.bucket AND ct.completed_at < b.bucket + interval '1 {unit}'
+                LEFT JOIN tasks t ON t.id = ct.task_id
+                GROUP BY b.bucket ORDER BY b.bucket",
+                unit = period.unit()
+            ),
+            &[&periods],
+        )
+        .await?
+        .into_iter()
+        .map(|row| {
+            let bucket: DateTime<Utc> = row.get("bucket");
+            Velocity {
+                label: bucket.format(label_format).to_string().into(),
+                starts_on: bucket.date_naive(),
+                committed: None,
+                completed: row.get("completed"),
+                tasks: row.get("tasks"),
+            }
+        })
+        .collect())
+}
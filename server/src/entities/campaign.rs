@@ -0,0 +1,212 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+use tokio_postgres::Row;
+
+use super::task::{Task, TASK_COLUMNS};
+use crate::libs::db::DbClient;
+use tracing::instrument;
+
+#[derive(Serialize)]
+pub struct Campaign {
+    pub id: i32,
+    pub name: Box<str>,
+    pub goal: Box<str>,
+    pub starts_on: NaiveDate,
+    pub ends_on: NaiveDate,
+}
+
+impl From<Row> for Campaign {
+    fn from(row: Row) -> Self {
+        Campaign {
+            id: row.get("id"),
+            name: row.get("name"),
+            goal: row.get("goal"),
+            starts_on: row.get("starts_on"),
+            ends_on: row.get("ends_on"),
+        }
+    }
+}
+
+pub struct CampaignCreateData {
+    pub name: Box<str>,
+    pub goal: Box<str>,
+    pub starts_on: NaiveDate,
+    pub ends_on: NaiveDate,
+}
+
+#[derive(Serialize)]
+pub struct Summary {
+    pub planned_tasks: i64,
+    pub planned_hours: f64,
+    pub completed_tasks: i64,
+    pub completed_hours: f64,
+    pub spent_hours: f64,
+}
+
+#[derive(Serialize)]
+pub struct Contribution {
+    pub user_id: i32,
+    pub login: Box<str>,
+    pub name: Box<str>,
+    pub completed_tasks: i64,
+    pub hours: f64,
+    pub xp: i64,
+}
+
+#[instrument(level = "debug", skip(db_client, campaign))]
+pub async fn create(
+    db_client: &DbClient<'_>,
+    campaign: &CampaignCreateData,
+) -> Result<Campaign, tokio_postgres::Error> {
+    Ok(db_client
+        .query_one(
+            "INSERT INTO campaigns (name, goal, starts_on, ends_on) VALUES ($1, $2, $3, $4) RETURNING *",
+            &[
+                &campaign.name,
+                &campaign.goal,
+                &campaign.starts_on,
+                &campaign.ends_on,
+            ],
+        )
+        .await?
+        .into())
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get(db_client: &DbClient<'_>, id: i32) -> Result<Campaign, tokio_postgres::Error> {
+    Ok(db_client
+        .query_one("SELECT * FROM campaigns WHERE id = $1", &[&id])
+        .await?
+        .into())
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_all(db_client: &DbClient<'_>) -> Result<Vec<Campaign>, tokio_postgres::Error> {
+    Ok(db_client
+        .query(
+            "SELECT * FROM campaigns ORDER BY starts_on DESC, id DESC",
+            &[],
+        )
+        .await?
+        .into_iter()
+        .map(Campaign::from)
+        .collect())
+}
+
+/// Campaigns that have not ended yet, tasks can be planned into them.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_open(db_client: &DbClient<'_>) -> Result<Vec<Campaign>, tokio_postgres::Error> {
+    Ok(db_client
+        .query(
+            "SELECT * FROM campaigns WHERE ends_on >= CURRENT_DATE ORDER BY starts_on, id",
+            &[],
+        )
+        .await?
+        .into_iter()
+        .map(Campaign::from)
+        .collect())
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_next(
+    db_client: &DbClient<'_>,
+    id: i32,
+) -> Result<Option<Campaign>, tokio_postgres::Error> {
+    Ok(db_client
+        .query_opt(
+            "SELECT next.* FROM campaigns cur JOIN campaigns next ON next.starts_on > cur.starts_on
+                WHERE cur.id = $1 ORDER BY next.starts_on, next.id LIMIT 1",
+            &[&id],
+        )
+        .await?
+        .map(Campaign::from))
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_tasks(
+    db_client: &DbClient<'_>,
+    id: i32,
+) -> Result<Vec<Task>, tokio_postgres::Error> {
+    Ok(db_client
+        .query(
+            &format!("SELECT {TASK_COLUMNS} FROM tasks WHERE campaign_id = $1 ORDER BY id"),
+            &[&id],
+        )
+        .await?
+        .into_iter()
+        .map(Task::from)
+        .collect())
+}
+
+/// Moves unfinished tasks of the campaign into the next one.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn carry_over(
+    db_client: &DbClient<'_>,
+    from: i32,
+    to: i32,
+) -> Result<u64, tokio_postgres::Error> {
+    db_client
+        .execute(
+            "UPDATE tasks SET campaign_id = $2 WHERE campaign_id = $1
+                AND NOT EXISTS (SELECT 1 FROM completed_tasks WHERE task_id = tasks.id)",
+            &[&from, &to],
+        )
+        .await
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_summary(
+    db_client: &DbClient<'_>,
+    id: i32,
+) -> Result<Summary, tokio_postgres::Error> {
+    let row = db_client
+        .query_one(
+            "SELECT COUNT(*) AS planned_tasks,
+                COALESCE(SUM(expected_time), 0)::float8 AS planned_hours,
+                COUNT(ct.id) AS completed_tasks,
+                COALESCE(SUM(expected_time) FILTER (WHERE ct.id IS NOT NULL), 0)::float8 AS completed_hours,
+                COALESCE((SELECT SUM(EXTRACT(EPOCH FROM (COALESCE(stopped_at, now()) - started_at))) / 3600
+                    FROM worklogs JOIN tasks t ON t.id = worklogs.task_id WHERE t.campaign_id = $1), 0)::float8 AS spent_hours
+            FROM tasks LEFT JOIN completed_tasks ct ON ct.task_id = tasks.id
+            WHERE tasks.campaign_id = $1",
+            &[&id],
+        )
+        .await?;
+
+    Ok(Summary {
+        planned_tasks: row.get("planned_tasks"),
+        planned_hours: row.get("planned_hours"),
+        completed_tasks: row.get("completed_tasks"),
+        completed_hours: row.get("completed_hours"),
+        spent_hours: row.get("spent_hours"),
+    })
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_contributions(
+    db_client: &DbClient<'_>,
+    id: i32,
+) -> Result<Vec<Contribution>, tokio_postgres::Error> {
+    Ok(db_client
+        .query(
+            "SELECT users.id, users.login, users.name, COUNT(*) AS completed_tasks,
+                SUM(tasks.expected_time)::float8 AS hours, COALESCE(SUM(ct.xp), 0) AS xp
+            FROM completed_tasks ct
+                JOIN tasks ON tasks.id = ct.task_id
+                JOIN users ON users.id = ct.user_id
+            WHERE tasks.campaign_id = $1
+            GROUP BY users.id ORDER BY completed_tasks DESC, xp DESC",
+            &[&id],
+        )
+        .await?
+        .into_iter()
+        .map(|row| Contribution {
+            user_id: row.get("id"),
+            login: row.get("login"),
+            name: row.get("name"),
+            completed_tasks: row.get("completed_tasks"),
+            hours: row.get("hours"),
+            xp: row.get("xp"),
+        })
+        .collect())
+}
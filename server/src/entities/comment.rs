@@ -0,0 +1,152 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio_postgres::Row;
+
+use crate::libs::db::DbClient;
+use tracing::instrument;
+
+#[derive(Serialize)]
+pub struct Comment {
+    pub id: i32,
+    pub task_id: i32,
+    pub author_id: Option<i32>,
+    pub author_login: Option<Box<str>>,
+    pub author_name: Option<Box<str>>,
+    pub body: Box<str>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub mentions: Vec<Box<str>>,
+}
+
+impl From<Row> for Comment {
+    fn from(row: Row) -> Self {
+        Comment {
+            id: row.get("id"),
+            task_id: row.get("task_id"),
+            author_id: row.get("author_id"),
+            author_login: row.get("author_login"),
+            author_name: row.get("author_name"),
+            body: row.get("body"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            mentions: row.get("mentions"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Mention {
+    pub comment_id: i32,
+    pub task_id: i32,
+    pub author_login: Option<Box<str>>,
+    pub created_at: DateTime<Utc>,
+}
+
+const COMMENT_COLUMNS: &str = "c.*, users.login AS author_login, users.name AS author_name,
+    ARRAY(SELECT u.login FROM comment_mentions m JOIN users u ON u.id = m.user_id WHERE m.comment_id = c.id) AS mentions";
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get(db_client: &DbClient<'_>, id: i32) -> Result<Comment, tokio_postgres::Error> {
+    Ok(db_client
+        .query_one(
+            &format!("SELECT {COMMENT_COLUMNS} FROM task_comments c LEFT JOIN users ON users.id = c.author_id WHERE c.id = $1"),
+            &[&id],
+        )
+        .await?
+        .into())
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_by_task(
+    db_client: &DbClient<'_>,
+    task_id: i32,
+) -> Result<Vec<Comment>, tokio_postgres::Error> {
+    Ok(db_client
+        .query(
+            &format!("SELECT {COMMENT_COLUMNS} FROM task_comments c LEFT JOIN users ON users.id = c.author_id WHERE c.task_id = $1 ORDER BY c.created_at, c.id"),
+            &[&task_id],
+        )
+        .await?
+        .into_iter()
+        .map(Comment::from)
+        .collect())
+}
+
+#[instrument(level = "debug", skip(db_client, body))]
+pub async fn create(
+    db_client: &DbClient<'_>,
+    task_id: i32,
+    author_id: i32,
+    body: &str,
+    mentions: &[&str],
+) -> Result<Comment, tokio_postgres::Error> {
+    let id: i32 = db_client
+        .query_one(
+            "WITH c AS (
+                INSERT INTO task_comments (task_id, author_id, body) VALUES ($1, $2, $3) RETURNING id
+            ), m AS (
+                INSERT INTO comment_mentions (comment_id, user_id) SELECT c.id, users.id FROM c, users WHERE users.login = ANY($4)
+            )
+            SELECT id FROM c",
+            &[&task_id, &author_id, &body, &mentions],
+        )
+        .await?
+        .get("id");
+
+    get(db_client, id).await
+}
+
+#[instrument(level = "debug", skip(db_client, body))]
+pub async fn update(
+    db_client: &DbClient<'_>,
+    id: i32,
+    body: &str,
+    mentions: &[&str],
+) -> Result<Comment, tokio_postgres::Error> {
+    db_client
+        .execute(
+            "WITH c AS (
+                UPDATE task_comments SET body = $2, updated_at = now() WHERE id = $1 RETURNING id
+            ), old AS (
+                DELETE FROM comment_mentions WHERE comment_id IN (SELECT id FROM c)
+                    AND user_id NOT IN (SELECT id FROM users WHERE login = ANY($3))
+            )
+            INSERT INTO comment_mentions (comment_id, user_id) SELECT c.id, users.id FROM c, users WHERE users.login = ANY($3)
+                ON CONFLICT DO NOTHING",
+            &[&id, &body, &mentions],
+        )
+        .await?;
+
+    get(db_client, id).await
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn delete(db_client: &DbClient<'_>, id: i32) -> Result<u64, tokio_postgres::Error> {
+    db_client
+        .execute("DELETE FROM task_comments WHERE id = $1", &[&id])
+        .await
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_mentions_of(
+    db_client: &DbClient<'_>,
+    user_id: i32,
+) -> Result<Vec<Mention>, tokio_postgres::Error> {
+    Ok(db_client
+        .query(
+            "SELECT c.id, c.task_id, users.login, c.created_at FROM comment_mentions m
+                JOIN task_comments c ON c.id = m.comment_id
+                LEFT JOIN users ON users.id = c.author_id
+            WHERE m.user_id = $1 ORDER BY c.created_at DESC LIMIT 10",
+            &[&user_id],
+        )
+        .await?
+        .into_iter()
+        .map(|row| Mention {
+            comment_id: row.get("id"),
+            task_id: row.get("task_id"),
+            author_login: row.get("login"),
+            created_at: row.get("created_at"),
+        })
+        .collect())
+}
@@ -0,0 +1,126 @@
+use tokio_postgres::Transaction;
+
+use crate::libs::db::DbClient;
+use tracing::instrument;
+
+// "a needs b" edges: explicit blockers and parents waiting for their sub-quests
+const EDGES: &str = "edges(id, dep) AS (
+    SELECT task_id, blocked_by FROM task_dependencies
+    UNION ALL
+    SELECT parent_id, id FROM tasks WHERE parent_id IS NOT NULL
+)";
+
+// every change of the graph holds this lock, so two changes can't close a loop together
+const GRAPH_LOCK: &str = "SELECT pg_advisory_xact_lock(hashtext('task_dependencies'))";
+
+// whether making `task_id` wait for `dep_id` would close a loop
+async fn closes_loop(
+    tx: &Transaction<'_>,
+    task_id: i32,
+    dep_id: i32,
+) -> Result<bool, tokio_postgres::Error> {
+    Ok(tx
+        .query_one(
+            &format!(
+                "WITH RECURSIVE {EDGES}, reachable(id) AS (
+                    SELECT $2::int
+                    UNION
+                    SELECT edges.dep FROM edges JOIN reachable ON edges.id = reachable.id
+                )
+                SELECT EXISTS (SELECT 1 FROM reachable WHERE id = $1) AS cycle"
+            ),
+            &[&task_id, &dep_id],
+        )
+        .await?
+        .get("cycle"))
+}
+
+/// Makes `task_id` wait for `blocked_by`. Returns false, changing nothing, if that would close a loop.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn add(
+    db_client: &mut DbClient<'_>,
+    task_id: i32,
+    blocked_by: i32,
+) -> Result<bool, tokio_postgres::Error> {
+    let tx = db_client.transaction().await?;
+    tx.execute(GRAPH_LOCK, &[]).await?;
+    if closes_loop(&tx, task_id, blocked_by).await? {
+        return Ok(false);
+    }
+    tx.execute(
+        "INSERT INTO task_dependencies (task_id, blocked_by) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        &[&task_id, &blocked_by],
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(true)
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn remove(
+    db_client: &DbClient<'_>,
+    task_id: i32,
+    blocked_by: i32,
+) -> Result<u64, tokio_postgres::Error> {
+    db_client
+        .execute(
+            "DELETE FROM task_dependencies WHERE task_id = $1 AND blocked_by = $2",
+            &[&task_id, &blocked_by],
+        )
+        .await
+}
+
+/// Attaches the task to a parent, who then waits for it, or detaches it with `None`.
+/// Returns false, changing nothing, if that would close a loop.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn set_parent(
+    db_client: &mut DbClient<'_>,
+    task_id: i32,
+    parent_id: Option<i32>,
+) -> Result<bool, tokio_postgres::Error> {
+    let tx = db_client.transaction().await?;
+    tx.execute(GRAPH_LOCK, &[]).await?;
+    if let Some(parent_id) = parent_id {
+        if closes_loop(&tx, parent_id, task_id).await? {
+            return Ok(false);
+        }
+    }
+    tx.execute(
+        "UPDATE tasks SET parent_id = $1 WHERE id = $2",
+        &[&parent_id, &task_id],
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(true)
+}
+
+/// A task is blocked while any of its blockers is not completed.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn is_blocked(
+    db_client: &DbClient<'_>,
+    task_id: i32,
+) -> Result<bool, tokio_postgres::Error> {
+    Ok(db_client
+        .query_one(
+            "SELECT EXISTS (SELECT 1 FROM task_dependencies d WHERE d.task_id = $1
+                AND NOT EXISTS (SELECT 1 FROM completed_tasks WHERE task_id = d.blocked_by)) AS blocked",
+            &[&task_id],
+        )
+        .await?
+        .get("blocked"))
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn has_unfinished_children(
+    db_client: &DbClient<'_>,
+    task_id: i32,
+) -> Result<bool, tokio_postgres::Error> {
+    Ok(db_client
+        .query_one(
+            "SELECT EXISTS (SELECT 1 FROM tasks WHERE parent_id = $1
+                AND NOT EXISTS (SELECT 1 FROM completed_tasks WHERE task_id = tasks.id)) AS unfinished",
+            &[&task_id],
+        )
+        .await?
+        .get("unfinished"))
+}
@@ -1,7 +1,9 @@
 use nanoid::nanoid;
 
 use crate::libs::db::DbClient;
+use tracing::instrument;
 
+#[instrument(level = "debug", skip(db_client))]
 pub async fn create(db_client: &DbClient<'_>) -> Result<Box<str>, tokio_postgres::Error> {
     let token = tokio::task::spawn_blocking(move || nanoid!())
         .await
@@ -17,6 +19,7 @@ pub async fn create(db_client: &DbClient<'_>) -> Result<Box<str>, tokio_postgres
     Ok(token.into())
 }
 
+#[instrument(level = "debug", skip(db_client, token))]
 pub async fn is_expired(
     db_client: &DbClient<'_>,
     token: &str,
@@ -30,6 +33,7 @@ pub async fn is_expired(
         .get("is_expired"))
 }
 
+#[instrument(level = "debug", skip(db_client, token))]
 pub async fn expire(db_client: &DbClient<'_>, token: &str) -> Result<u64, tokio_postgres::Error> {
     db_client
         .execute(
@@ -0,0 +1,104 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio_postgres::Row;
+
+use crate::libs::db::DbClient;
+use tracing::instrument;
+
+#[derive(Serialize)]
+pub struct JobStatus {
+    pub name: Box<str>,
+    pub schedule: Box<str>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub running: bool,
+    pub succeeded: Option<bool>,
+    /// What the last run did or why it failed.
+    pub report: Option<Box<str>>,
+    pub runs: i32,
+    pub failures: i32,
+}
+
+impl From<Row> for JobStatus {
+    fn from(row: Row) -> Self {
+        JobStatus {
+            name: row.get("name"),
+            schedule: row.get("schedule"),
+            started_at: row.get("started_at"),
+            finished_at: row.get("finished_at"),
+            running: row.get("running"),
+            succeeded: row.get("succeeded"),
+            report: row.get("report"),
+            runs: row.get("runs"),
+            failures: row.get("failures"),
+        }
+    }
+}
+
+/// Adds the job to the list or updates its schedule.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn register(
+    db_client: &DbClient<'_>,
+    name: &str,
+    schedule: &str,
+) -> Result<u64, tokio_postgres::Error> {
+    db_client
+        .execute(
+            "INSERT INTO jobs (name, schedule) VALUES ($1, $2)
+                ON CONFLICT (name) DO UPDATE SET schedule = EXCLUDED.schedule",
+            &[&name, &schedule],
+        )
+        .await
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_started_at(
+    db_client: &DbClient<'_>,
+    name: &str,
+) -> Result<Option<DateTime<Utc>>, tokio_postgres::Error> {
+    Ok(db_client
+        .query_opt("SELECT started_at FROM jobs WHERE name = $1", &[&name])
+        .await?
+        .and_then(|row| row.get("started_at")))
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn start(db_client: &DbClient<'_>, name: &str) -> Result<u64, tokio_postgres::Error> {
+    db_client
+        .execute(
+            "UPDATE jobs SET started_at = now(), runs = runs + 1 WHERE name = $1",
+            &[&name],
+        )
+        .await
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn finish(
+    db_client: &DbClient<'_>,
+    name: &str,
+    succeeded: bool,
+    report: &str,
+) -> Result<u64, tokio_postgres::Error> {
+    db_client
+        .execute(
+            "UPDATE jobs SET finished_at = now(), succeeded = $2, report = $3,
+                failures = failures + CASE WHEN $2 THEN 0 ELSE 1 END
+            WHERE name = $1",
+            &[&name, &succeeded, &report],
+        )
+        .await
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_all(db_client: &DbClient<'_>) -> Result<Vec<JobStatus>, tokio_postgres::Error> {
+    Ok(db_client
+        .query(
+            "SELECT *, started_at IS NOT NULL AND (finished_at IS NULL OR finished_at < started_at) AS running
+                FROM jobs ORDER BY name",
+            &[],
+        )
+        .await?
+        .into_iter()
+        .map(JobStatus::from)
+        .collect())
+}
@@ -0,0 +1,196 @@
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Row;
+
+use super::{
+    page::{empty_as_none, Cursor, Page},
+    user::Class,
+};
+use crate::libs::db::DbClient;
+use tracing::instrument;
+
+pub const LEADERBOARD_SIZE: i64 = 10;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Period {
+    Sprint,
+    Week,
+    Month,
+    #[default]
+    All,
+}
+
+impl Period {
+    // which completions count, the bare completed_at keeps its indexes usable.
+    // Completions from before completed_at was recorded count for all time only
+    fn condition(&self) -> &'static str {
+        match self {
+            // the current campaign, or a rolling two-week iteration without one
+            Period::Sprint => {
+                "ct.completed_at >= COALESCE((SELECT starts_on::timestamptz FROM campaigns
+                    WHERE CURRENT_DATE BETWEEN starts_on AND ends_on ORDER BY starts_on DESC LIMIT 1),
+                    now() - interval '14 days')"
+            }
+            Period::Week => "ct.completed_at >= date_trunc('week', now())",
+            Period::Month => "ct.completed_at >= date_trunc('month', now())",
+            Period::All => "TRUE",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    #[default]
+    Count,
+    Xp,
+    Hours,
+    ARank,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 4] = [Metric::Count, Metric::Xp, Metric::Hours, Metric::ARank];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Metric::Count => "count",
+            Metric::Xp => "xp",
+            Metric::Hours => "hours",
+            Metric::ARank => "a_rank",
+        }
+    }
+
+    fn score(&self) -> &'static str {
+        match self {
+            Metric::Count => "COUNT(*)::float8",
+            Metric::Xp => "SUM(ct.xp)::float8",
+            Metric::Hours => "SUM(t.expected_time)::float8",
+            Metric::ARank => "(COUNT(*) FILTER (WHERE t.complexity = 2))::float8",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Filter {
+    #[serde(default)]
+    pub period: Period,
+    #[serde(default)]
+    pub metric: Metric,
+    pub tag: Option<Box<str>>,
+    /// The form sends an empty value for all classes.
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub class: Option<Class>,
+}
+
+impl Filter {
+    fn tag(&self) -> Option<&str> {
+        self.tag.as_deref().map(str::trim).filter(|t| !t.is_empty())
+    }
+
+    fn class(&self) -> Option<i16> {
+        self.class.map(|c| c.into())
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Entry {
+    pub rank: i64,
+    pub id: i32,
+    pub login: Box<str>,
+    pub name: Box<str>,
+    pub class: Class,
+    pub score: f64,
+}
+
+impl From<Row> for Entry {
+    fn from(row: Row) -> Self {
+        Entry {
+            rank: row.get("rank"),
+            id: row.get("id"),
+            login: row.get("login"),
+            name: row.get("name"),
+            class: row.get::<&str, i16>("class").into(),
+            score: row.get("score"),
+        }
+    }
+}
+
+// every user gets a rank, so the caller can be located even with zero score;
+// equal scores go to whoever reached theirs first, then to the older member
+fn ranking_query(filter: &Filter, tail: &str) -> String {
+    format!(
+        "WITH scores AS (
+            SELECT ct.user_id, {score} AS score, MAX(ct.completed_at) AS reached_at
+            FROM completed_tasks ct JOIN tasks t ON t.id = ct.task_id
+            WHERE {period} AND ($1::text IS NULL OR t.tags && tag_subtree($1))
+            GROUP BY ct.user_id
+        ), ranked AS (
+            SELECT u.id, u.login, u.name, u.class, COALESCE(s.score, 0) AS score,
+                RANK() OVER (ORDER BY COALESCE(s.score, 0) DESC, s.reached_at NULLS LAST, u.created_at, u.id) AS rank
+            FROM users u LEFT JOIN scores s ON s.user_id = u.id
+            WHERE ($2::smallint IS NULL OR u.class = $2)
+        )
+        SELECT * FROM ranked {tail}",
+        score = filter.metric.score(),
+        period = filter.period.condition(),
+    )
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn top(
+    db_client: &DbClient<'_>,
+    filter: &Filter,
+    cursor: &Cursor,
+) -> Result<Page<Entry>, tokio_postgres::Error> {
+    let limit = cursor.limit(LEADERBOARD_SIZE);
+    let query = ranking_query(
+        filter,
+        "WHERE ($3::int IS NULL OR (rank, id) > (SELECT rank, id FROM ranked WHERE id = $3))
+            ORDER BY rank, id LIMIT $4",
+    );
+    let entries = db_client
+        .query(
+            &query,
+            &[&filter.tag(), &filter.class(), &cursor.after, &(limit + 1)],
+        )
+        .await?
+        .into_iter()
+        .map(Entry::from)
+        .collect();
+
+    Ok(Page::new(entries, limit, |e| e.id))
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn rank_of(
+    db_client: &DbClient<'_>,
+    filter: &Filter,
+    user_id: i32,
+) -> Result<Option<Entry>, tokio_postgres::Error> {
+    let query = ranking_query(filter, "WHERE id = $3");
+    Ok(db_client
+        .query_opt(&query, &[&filter.tag(), &filter.class(), &user_id])
+        .await?
+        .map(Entry::from))
+}
+
+/// Stores today's all-time standings by the metric, taken again the same day it replaces them.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn snapshot(
+    db_client: &DbClient<'_>,
+    metric: Metric,
+) -> Result<u64, tokio_postgres::Error> {
+    let filter = Filter {
+        metric,
+        ..Filter::default()
+    };
+    let query = format!(
+        "INSERT INTO leaderboard_snapshots (taken_on, metric, user_id, score, rank)
+            SELECT CURRENT_DATE, $3, id, score, rank FROM ({}) standings
+            ON CONFLICT (taken_on, metric, user_id) DO UPDATE SET score = EXCLUDED.score, rank = EXCLUDED.rank",
+        ranking_query(&filter, "")
+    );
+    db_client
+        .execute(&query, &[&filter.tag(), &filter.class(), &metric.as_str()])
+        .await
+}
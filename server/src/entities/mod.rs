@@ -1,3 +1,19 @@
+pub mod analytics;
+pub mod attachment;
+pub mod audit;
+pub mod burndown;
+pub mod campaign;
+pub mod comment;
+pub mod dependency;
 pub mod invite;
+pub mod job;
+pub mod leaderboard;
+pub mod page;
+pub mod recommendation;
+pub mod session;
+pub mod skill;
+pub mod tag;
 pub mod task;
+pub mod transfer;
 pub mod user;
+pub mod worklog;
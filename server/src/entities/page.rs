@@ -0,0 +1,57 @@
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+const MAX_PAGE_SIZE: i64 = 100;
+
+// html forms send empty strings for untouched inputs
+pub fn empty_as_none<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(v) if !v.trim().is_empty() => v
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| de::Error::custom("invalid value")),
+        _ => Ok(None),
+    }
+}
+
+/// Keyset cursor: the id of the last seen row. Queries compare their sort key
+/// with the key of that row, so pages stay stable while rows are added.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default)]
+pub struct Cursor {
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub after: Option<i32>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub limit: Option<i64>,
+}
+
+impl Cursor {
+    pub fn limit(&self, default: i64) -> i64 {
+        self.limit.unwrap_or(default).clamp(1, MAX_PAGE_SIZE)
+    }
+}
+
+#[derive(Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<i32>,
+}
+
+impl<T> Page<T> {
+    /// Builds a page from `limit + 1` fetched rows, the extra row only tells there is more.
+    pub fn new(mut items: Vec<T>, limit: i64, id: impl Fn(&T) -> i32) -> Page<T> {
+        let next_cursor = if items.len() as i64 > limit {
+            items.truncate(limit as usize);
+            items.last().map(id)
+        } else {
+            None
+        };
+
+        Page { items, next_cursor }
+    }
+}
@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+
+use crate::libs::db::DbClient;
+use tracing::instrument;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Vote {
+    Up,
+    Down,
+    /// Never recommend the quest again.
+    Hide,
+}
+
+impl Vote {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Vote::Up => "up",
+            Vote::Down => "down",
+            Vote::Hide => "hide",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Vote> {
+        match s {
+            "up" => Some(Vote::Up),
+            "down" => Some(Vote::Down),
+            "hide" => Some(Vote::Hide),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Acceptance {
+    /// Recommended quests, each counted once per user.
+    pub shown: i64,
+    /// Recommended quests the user then took.
+    pub accepted: i64,
+    pub rate: f64,
+    pub up: i64,
+    pub down: i64,
+    pub hidden: i64,
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn vote(
+    db_client: &DbClient<'_>,
+    user_id: i32,
+    task_id: i32,
+    vote: Vote,
+) -> Result<u64, tokio_postgres::Error> {
+    db_client
+        .execute(
+            "INSERT INTO recommendation_feedback (user_id, task_id, vote) VALUES ($1, $2, $3)
+                ON CONFLICT (user_id, task_id) DO UPDATE SET vote = EXCLUDED.vote, created_at = now()",
+            &[&user_id, &task_id, &vote.as_str()],
+        )
+        .await
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn unvote(
+    db_client: &DbClient<'_>,
+    user_id: i32,
+    task_id: i32,
+) -> Result<u64, tokio_postgres::Error> {
+    db_client
+        .execute(
+            "DELETE FROM recommendation_feedback WHERE user_id = $1 AND task_id = $2",
+            &[&user_id, &task_id],
+        )
+        .await
+}
+
+/// Votes of the user on quests that are still on the board.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_votes(
+    db_client: &DbClient<'_>,
+    user_id: i32,
+) -> Result<Vec<(i32, Vote)>, tokio_postgres::Error> {
+    Ok(db_client
+        .query(
+            "SELECT f.task_id, f.vote FROM recommendation_feedback f JOIN tasks ON tasks.id = f.task_id
+                WHERE f.user_id = $1 AND tasks.assigned_to IS NULL ORDER BY f.task_id",
+            &[&user_id],
+        )
+        .await?
+        .into_iter()
+        .filter_map(|row| Some((row.get("task_id"), Vote::parse(row.get("vote"))?)))
+        .collect())
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn log_shown(
+    db_client: &DbClient<'_>,
+    user_id: i32,
+    shown: &[(i32, Option<f32>)],
+) -> Result<u64, tokio_postgres::Error> {
+    let (task_ids, scores): (Vec<i32>, Vec<Option<f32>>) = shown.iter().copied().unzip();
+    db_client
+        .execute(
+            "INSERT INTO recommendation_impressions (user_id, task_id, score)
+                SELECT $1, * FROM unnest($2::int[], $3::real[])
+                ON CONFLICT (user_id, task_id) DO UPDATE SET score = EXCLUDED.score, shown_at = now()",
+            &[&user_id, &task_ids, &scores],
+        )
+        .await
+}
+
+/// Marks the quest as taken, true if it was recommended to the user.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn log_accepted(
+    db_client: &DbClient<'_>,
+    user_id: i32,
+    task_id: i32,
+) -> Result<bool, tokio_postgres::Error> {
+    Ok(db_client
+        .execute(
+            "UPDATE recommendation_impressions SET accepted_at = now()
+                WHERE user_id = $1 AND task_id = $2 AND accepted_at IS NULL",
+            &[&user_id, &task_id],
+        )
+        .await?
+        > 0)
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_acceptance(db_client: &DbClient<'_>) -> Result<Acceptance, tokio_postgres::Error> {
+    let row = db_client
+        .query_one(
+            "SELECT COUNT(*) AS shown, COUNT(accepted_at) AS accepted,
+                (SELECT COUNT(*) FROM recommendation_feedback WHERE vote = 'up') AS up,
+                (SELECT COUNT(*) FROM recommendation_feedback WHERE vote = 'down') AS down,
+                (SELECT COUNT(*) FROM recommendation_feedback WHERE vote = 'hide') AS hidden
+            FROM recommendation_impressions",
+            &[],
+        )
+        .await?;
+    let shown: i64 = row.get("shown");
+    let accepted: i64 = row.get("accepted");
+
+    Ok(Acceptance {
+        shown,
+        accepted,
+        rate: if shown > 0 {
+            accepted as f64 / shown as f64
+        } else {
+            0.0
+        },
+        up: row.get("up"),
+        down: row.get("down"),
+        hidden: row.get("hidden"),
+    })
+}
@@ -0,0 +1,68 @@
+use chrono::{DateTime, Utc};
+
+use crate::libs::db::DbClient;
+use tracing::instrument;
+
+// ids are secrets and the data holds the auth hash, neither gets into the logs
+
+/// False when the id is taken.
+#[instrument(level = "debug", skip_all)]
+pub async fn create(
+    db_client: &DbClient<'_>,
+    id: &str,
+    data: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<bool, tokio_postgres::Error> {
+    Ok(db_client
+        .execute(
+            "INSERT INTO sessions (id, data, expires_at) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+            &[&id, &data, &expires_at],
+        )
+        .await?
+        > 0)
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn save(
+    db_client: &DbClient<'_>,
+    id: &str,
+    data: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<u64, tokio_postgres::Error> {
+    db_client
+        .execute(
+            "INSERT INTO sessions (id, data, expires_at) VALUES ($1, $2, $3)
+                ON CONFLICT (id) DO UPDATE SET data = EXCLUDED.data, expires_at = EXCLUDED.expires_at",
+            &[&id, &data, &expires_at],
+        )
+        .await
+}
+
+/// Data and expiry of the session unless it has expired.
+#[instrument(level = "debug", skip_all)]
+pub async fn load(
+    db_client: &DbClient<'_>,
+    id: &str,
+) -> Result<Option<(String, DateTime<Utc>)>, tokio_postgres::Error> {
+    Ok(db_client
+        .query_opt(
+            "SELECT data, expires_at FROM sessions WHERE id = $1 AND expires_at > now()",
+            &[&id],
+        )
+        .await?
+        .map(|row| (row.get("data"), row.get("expires_at"))))
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn delete(db_client: &DbClient<'_>, id: &str) -> Result<u64, tokio_postgres::Error> {
+    db_client
+        .execute("DELETE FROM sessions WHERE id = $1", &[&id])
+        .await
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn delete_expired(db_client: &DbClient<'_>) -> Result<u64, tokio_postgres::Error> {
+    db_client
+        .execute("DELETE FROM sessions WHERE expires_at <= now()", &[])
+        .await
+}
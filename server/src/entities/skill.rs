@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio_postgres::Row;
+
+use crate::libs::db::DbClient;
+use tracing::instrument;
+
+// a quest counts half as much after this many days
+const HALF_LIFE_DAYS: f64 = 90.0;
+// skills that faded below this are forgotten
+const MIN_SCORE: f64 = 0.1;
+
+#[derive(Serialize)]
+pub struct Skill {
+    pub tag: Box<str>,
+    pub score: f64,
+    pub level: &'static str,
+    pub quests: i64,
+    pub last_used_at: DateTime<Utc>,
+}
+
+fn level(score: f64) -> &'static str {
+    match score {
+        s if s < 2.0 => "Новичок",
+        s if s < 5.0 => "Подмастерье",
+        s if s < 10.0 => "Умелец",
+        s if s < 20.0 => "Мастер",
+        _ => "Легенда",
+    }
+}
+
+// one tag of one completed quest
+struct Use {
+    tag: Box<str>,
+    complexity: i16,
+    at: DateTime<Utc>,
+}
+
+impl From<Row> for Use {
+    fn from(row: Row) -> Self {
+        Use {
+            tag: row.get("tag"),
+            complexity: row.get("complexity"),
+            at: row.get("used_at"),
+        }
+    }
+}
+
+fn decayed(complexity: i16, at: DateTime<Utc>, now: DateTime<Utc>) -> f64 {
+    let days = (now - at).num_seconds() as f64 / 86400.0;
+    f64::from(complexity + 1) * 0.5f64.powf(days / HALF_LIFE_DAYS)
+}
+
+fn aggregate(uses: Vec<Use>, now: DateTime<Utc>) -> Vec<Skill> {
+    let mut by_tag: HashMap<Box<str>, (f64, i64, DateTime<Utc>)> = HashMap::new();
+    for u in uses {
+        let score = decayed(u.complexity, u.at, now);
+        let e = by_tag.entry(u.tag).or_insert((0.0, 0, u.at));
+        e.0 += score;
+        e.1 += 1;
+        e.2 = e.2.max(u.at);
+    }
+
+    let mut skills: Vec<Skill> = by_tag
+        .into_iter()
+        .filter(|(_, (score, _, _))| *score >= MIN_SCORE)
+        .map(|(tag, (score, quests, last_used_at))| Skill {
+            tag,
+            score,
+            level: level(score),
+            quests,
+            last_used_at,
+        })
+        .collect();
+    skills.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.tag.cmp(&b.tag)));
+    skills
+}
+
+/// Proficiency per tag: every completed quest adds its complexity level (C = 1, A = 3),
+/// halved for each `HALF_LIFE_DAYS` since completion. Completions older than
+/// `completed_at` count from the creation of their quest, the earliest time known for them.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_by_user(
+    db_client: &DbClient<'_>,
+    user_id: i32,
+) -> Result<Vec<Skill>, tokio_postgres::Error> {
+    let uses = db_client
+        .query(
+            "SELECT tag, t.complexity, COALESCE(ct.completed_at, t.created_at) AS used_at
+            FROM completed_tasks ct JOIN tasks t ON t.id = ct.task_id, unnest(t.tags) tag
+            WHERE ct.user_id = $1",
+            &[&user_id],
+        )
+        .await?
+        .into_iter()
+        .map(Use::from)
+        .collect();
+
+    Ok(aggregate(uses, Utc::now()))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+
+    fn used(tag: &str, complexity: i16, at: DateTime<Utc>) -> Use {
+        Use {
+            tag: tag.into(),
+            complexity,
+            at,
+        }
+    }
+
+    #[test]
+    fn score_halves_every_half_life() {
+        let now = Utc::now();
+        let skills = aggregate(
+            vec![
+                used("rust", 2, now),
+                used("rust", 0, now - TimeDelta::days(HALF_LIFE_DAYS as i64)),
+            ],
+            now,
+        );
+        assert_eq!(skills.len(), 1);
+        assert!((skills[0].score - 3.5).abs() < 1e-6);
+        assert_eq!(skills[0].quests, 2);
+        assert_eq!(skills[0].last_used_at, now);
+    }
+
+    #[test]
+    fn completions_of_unknown_time_still_count() {
+        // what the query gives for a NULL completed_at: the creation of the quest
+        let created_at = Utc::now() - TimeDelta::days(10);
+        let now = created_at + TimeDelta::days(10);
+        let skills = aggregate(vec![used("docs", 1, created_at)], now);
+        assert_eq!(skills.len(), 1);
+        assert_eq!(&*skills[0].tag, "docs");
+        assert_eq!(skills[0].quests, 1);
+        assert!(skills[0].score > 1.8 && skills[0].score < 2.0);
+    }
+
+    #[test]
+    fn faded_skills_are_forgotten_and_the_rest_sorted() {
+        let now = Utc::now();
+        let skills = aggregate(
+            vec![
+                used("old", 0, now - TimeDelta::days(HALF_LIFE_DAYS as i64 * 5)),
+                used("b", 0, now),
+                used("a", 0, now),
+                used("top", 2, now),
+            ],
+            now,
+        );
+        let tags: Vec<&str> = skills.iter().map(|s| &*s.tag).collect();
+        assert_eq!(tags, ["top", "a", "b"]);
+    }
+}
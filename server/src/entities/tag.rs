@@ -0,0 +1,267 @@
+use serde::Serialize;
+use tokio_postgres::{GenericClient, Row};
+
+use crate::libs::db::DbClient;
+use tracing::instrument;
+
+pub const MAX_TAG_LEN: usize = 64;
+const SUGGESTIONS: i64 = 10;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("tag is longer than {MAX_TAG_LEN} characters")]
+    TooLong,
+
+    #[error(transparent)]
+    Db(#[from] tokio_postgres::Error),
+}
+
+#[derive(Serialize)]
+pub struct Tag {
+    pub id: i32,
+    pub name: Box<str>,
+    pub parent_id: Option<i32>,
+    pub parent_name: Option<Box<str>>,
+    pub synonyms: Vec<Box<str>>,
+}
+
+impl From<Row> for Tag {
+    fn from(row: Row) -> Self {
+        Tag {
+            id: row.get("id"),
+            name: row.get("name"),
+            parent_id: row.get("parent_id"),
+            parent_name: row.get("parent_name"),
+            synonyms: row.get("synonyms"),
+        }
+    }
+}
+
+const TAG_COLUMNS: &str = "tags.*, parent.name AS parent_name,
+    ARRAY(SELECT s.synonym FROM tag_synonyms s WHERE s.tag_id = tags.id ORDER BY s.synonym) AS synonyms";
+
+/// Splits user input into canonical tag names. Unknown tags join the catalog,
+/// synonyms and other spellings of known ones are replaced by the canonical name.
+/// Fails with `Error::TooLong` before touching the catalog if any tag is too long.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn normalize(db_client: &DbClient<'_>, input: &str) -> Result<Vec<Box<str>>, Error> {
+    let tags: Vec<&str> = input.split_whitespace().collect();
+    if tags.iter().any(|t| t.chars().count() > MAX_TAG_LEN) {
+        return Err(Error::TooLong);
+    }
+
+    db_client
+        .execute(
+            "INSERT INTO tags (name) SELECT DISTINCT ON (lower(t)) t FROM unnest($1::text[]) t
+                WHERE NOT EXISTS (SELECT 1 FROM tag_synonyms WHERE synonym = lower(t))
+                ORDER BY lower(t), t
+                ON CONFLICT DO NOTHING",
+            &[&tags],
+        )
+        .await?;
+    Ok(db_client
+        .query_one("SELECT normalize_tags($1) AS tags", &[&tags])
+        .await?
+        .get("tags"))
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get(db_client: &DbClient<'_>, id: i32) -> Result<Tag, tokio_postgres::Error> {
+    Ok(db_client
+        .query_one(
+            &format!("SELECT {TAG_COLUMNS} FROM tags LEFT JOIN tags parent ON parent.id = tags.parent_id WHERE tags.id = $1"),
+            &[&id],
+        )
+        .await?
+        .into())
+}
+
+/// Looks a tag up by any spelling of its name or by a synonym.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn find(
+    db_client: &DbClient<'_>,
+    name: &str,
+) -> Result<Option<Tag>, tokio_postgres::Error> {
+    Ok(db_client
+        .query_opt(
+            &format!("SELECT {TAG_COLUMNS} FROM tags LEFT JOIN tags parent ON parent.id = tags.parent_id WHERE lower(tags.name) = lower(canonical_tag($1))"),
+            &[&name],
+        )
+        .await?
+        .map(Tag::from))
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_all(db_client: &DbClient<'_>) -> Result<Vec<Tag>, tokio_postgres::Error> {
+    Ok(db_client
+        .query(
+            &format!("SELECT {TAG_COLUMNS} FROM tags LEFT JOIN tags parent ON parent.id = tags.parent_id ORDER BY lower(tags.name)"),
+            &[],
+        )
+        .await?
+        .into_iter()
+        .map(Tag::from)
+        .collect())
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn create(
+    db_client: &DbClient<'_>,
+    name: &str,
+    parent_id: Option<i32>,
+) -> Result<Tag, tokio_postgres::Error> {
+    let id: i32 = db_client
+        .query_one(
+            "INSERT INTO tags (name, parent_id) VALUES ($1, $2) RETURNING id",
+            &[&name, &parent_id],
+        )
+        .await?
+        .get("id");
+
+    get(db_client, id).await
+}
+
+/// Whether making `parent_id` the parent of `id` would loop the hierarchy.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn would_cycle(
+    db_client: &DbClient<'_>,
+    id: i32,
+    parent_id: i32,
+) -> Result<bool, tokio_postgres::Error> {
+    closes_loop(&**db_client, id, parent_id).await
+}
+
+async fn closes_loop(
+    client: &impl GenericClient,
+    id: i32,
+    parent_id: i32,
+) -> Result<bool, tokio_postgres::Error> {
+    Ok(client
+        .query_one(
+            "WITH RECURSIVE ancestors AS (
+                SELECT id, parent_id FROM tags WHERE id = $2
+                UNION
+                SELECT t.id, t.parent_id FROM tags t JOIN ancestors a ON t.id = a.parent_id
+            )
+            SELECT EXISTS (SELECT 1 FROM ancestors WHERE id = $1) AS cycle",
+            &[&id, &parent_id],
+        )
+        .await?
+        .get("cycle"))
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn set_parent(
+    db_client: &DbClient<'_>,
+    id: i32,
+    parent_id: Option<i32>,
+) -> Result<u64, tokio_postgres::Error> {
+    db_client
+        .execute(
+            "UPDATE tags SET parent_id = $2 WHERE id = $1",
+            &[&id, &parent_id],
+        )
+        .await
+}
+
+/// Adds a synonym and rewrites tasks that used it. A tag named
+/// like the synonym is merged into this one together with its children and synonyms.
+/// Returns false when the synonym is the name of the tag itself.
+/// Everything happens in one transaction, a failed merge leaves the catalog as it was.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn add_synonym(
+    db_client: &mut DbClient<'_>,
+    id: i32,
+    synonym: &str,
+) -> Result<bool, tokio_postgres::Error> {
+    let synonym = synonym.trim().to_lowercase();
+    let tx = db_client.transaction().await?;
+    let own_name = tx
+        .query_one(
+            "SELECT EXISTS (SELECT 1 FROM tags WHERE id = $1 AND lower(name) = $2) AS own",
+            &[&id, &synonym],
+        )
+        .await?
+        .get("own");
+    if own_name {
+        return Ok(false);
+    }
+
+    // merge the tag that had this name
+    if let Some(row) = tx
+        .query_opt("SELECT id FROM tags WHERE lower(name) = $1", &[&synonym])
+        .await?
+    {
+        let old: i32 = row.get("id");
+        // a descendant takes the place of the merged tag, or the children would loop
+        if closes_loop(&tx, old, id).await? {
+            tx
+                .execute(
+                    "UPDATE tags SET parent_id = (SELECT parent_id FROM tags WHERE id = $1) WHERE id = $2",
+                    &[&old, &id],
+                )
+                .await?;
+        }
+        tx.execute(
+            "UPDATE tags SET parent_id = $2 WHERE parent_id = $1 AND id <> $2",
+            &[&old, &id],
+        )
+        .await?;
+        tx.execute(
+            "UPDATE tag_synonyms SET tag_id = $2 WHERE tag_id = $1",
+            &[&old, &id],
+        )
+        .await?;
+        tx.execute("DELETE FROM tags WHERE id = $1", &[&old])
+            .await?;
+    }
+    tx.execute(
+        "INSERT INTO tag_synonyms (synonym, tag_id) VALUES ($1, $2)
+                ON CONFLICT (synonym) DO UPDATE SET tag_id = EXCLUDED.tag_id",
+        &[&synonym, &id],
+    )
+    .await?;
+    tx.execute(
+        "UPDATE tasks SET tags = normalize_tags(tags)
+            WHERE EXISTS (SELECT 1 FROM unnest(tags) t WHERE lower(btrim(t)) = $1)",
+        &[&synonym],
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn remove_synonym(
+    db_client: &DbClient<'_>,
+    synonym: &str,
+) -> Result<u64, tokio_postgres::Error> {
+    db_client
+        .execute(
+            "DELETE FROM tag_synonyms WHERE synonym = lower(btrim($1))",
+            &[&synonym],
+        )
+        .await
+}
+
+/// Canonical names starting with the prefix, synonyms included.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn suggest(
+    db_client: &DbClient<'_>,
+    prefix: &str,
+) -> Result<Vec<Box<str>>, tokio_postgres::Error> {
+    Ok(db_client
+        .query(
+            "SELECT name FROM (
+                SELECT name FROM tags WHERE starts_with(lower(name), lower($1))
+                UNION
+                SELECT t.name FROM tag_synonyms s JOIN tags t ON t.id = s.tag_id WHERE starts_with(s.synonym, lower($1))
+            ) found ORDER BY lower(name) LIMIT $2",
+            &[&prefix, &SUGGESTIONS],
+        )
+        .await?
+        .into_iter()
+        .map(|row| row.get("name"))
+        .collect())
+}
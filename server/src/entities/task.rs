@@ -1,10 +1,17 @@
-use std::{collections::HashSet, hash::RandomState};
-
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use tokio::join;
+use tokio_postgres::Row;
 
 use super::user::{Class, User};
-use crate::{entities::user, libs::db::DbClient};
+use crate::{
+    entities::{
+        page::{empty_as_none, Cursor, Page},
+        user, worklog,
+    },
+    libs::{config::Rules, db::DbClient},
+};
+use tracing::instrument;
 
 #[derive(Deserialize, Serialize)]
 pub struct Task {
@@ -14,15 +21,211 @@ pub struct Task {
     pub expected_time: f32,
     pub tags: Vec<Box<str>>,
     pub assigned_to: Option<i32>,
+    pub assigned_at: Option<DateTime<Utc>>,
+    pub spent_time: f32,
+    pub timer_running: bool,
+    pub due_at: Option<DateTime<Utc>>,
+    pub is_overdue: bool,
+    pub campaign_id: Option<i32>,
+    pub is_completed: bool,
+    pub parent_id: Option<i32>,
+    pub children: Vec<i32>,
+    pub blocked_by: Vec<i32>,
+    pub is_blocked: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl From<Row> for Task {
+    fn from(row: Row) -> Self {
+        Task {
+            id: row.get("id"),
+            complexity: row.get::<&str, i16>("complexity").into(),
+            description: row.get("description"),
+            expected_time: row.get("expected_time"),
+            tags: row.get("tags"),
+            assigned_to: row.get("assigned_to"),
+            assigned_at: row.get("assigned_at"),
+            spent_time: row.get("spent_time"),
+            timer_running: row.get("timer_running"),
+            due_at: row.get("due_at"),
+            is_overdue: row.get("is_overdue"),
+            campaign_id: row.get("campaign_id"),
+            is_completed: row.get("is_completed"),
+            parent_id: row.get("parent_id"),
+            children: row.get("children"),
+            blocked_by: row.get("blocked_by"),
+            is_blocked: row.get("is_blocked"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            completed_at: row.get("completed_at"),
+        }
+    }
 }
 
+// tasks.* extended with the tracked time, deadline state and quest chain, every task query selects it
+pub const TASK_COLUMNS: &str = "tasks.*,
+    ARRAY(SELECT c.id FROM tasks c WHERE c.parent_id = tasks.id ORDER BY c.id) AS children,
+    ARRAY(SELECT d.blocked_by FROM task_dependencies d WHERE d.task_id = tasks.id ORDER BY d.blocked_by) AS blocked_by,
+    EXISTS (SELECT 1 FROM task_dependencies d WHERE d.task_id = tasks.id
+        AND NOT EXISTS (SELECT 1 FROM completed_tasks WHERE task_id = d.blocked_by)) AS is_blocked,
+    COALESCE(tasks.due_at < now(), false) AS is_overdue,
+    EXISTS (SELECT 1 FROM completed_tasks WHERE task_id = tasks.id) AS is_completed,
+    (SELECT completed_at FROM completed_tasks WHERE task_id = tasks.id) AS completed_at,
+    (SELECT CAST(COALESCE(SUM(EXTRACT(EPOCH FROM (COALESCE(stopped_at, now()) - started_at))), 0) / 3600 AS REAL)
+        FROM worklogs WHERE task_id = tasks.id) AS spent_time,
+    EXISTS (SELECT 1 FROM worklogs WHERE task_id = tasks.id AND stopped_at IS NULL) AS timer_running";
+
 pub struct TaskCreateData {
     pub complexity: Class,
     pub expected_time: f32,
     pub tags: Vec<Box<str>>,
     pub description: Box<str>,
+    pub due_at: Option<DateTime<Utc>>,
+    pub campaign_id: Option<i32>,
+    pub parent_id: Option<i32>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Sort {
+    #[default]
+    Newest,
+    Id,
+    Urgency,
+    Relevance,
+    TimeAsc,
+    TimeDesc,
+    ComplexityAsc,
+    ComplexityDesc,
+}
+
+impl Sort {
+    // sort key ending with the id, pages continue after the key of the cursor row
+    fn key(&self) -> &'static str {
+        match self {
+            Sort::Newest => "tasks.created_at, tasks.id",
+            Sort::Id => "tasks.id",
+            Sort::Urgency => "COALESCE(tasks.due_at, 'infinity'), tasks.id",
+            Sort::Relevance => {
+                "-COALESCE(ts_rank(tasks.search, websearch_to_tsquery('russian', $1) || websearch_to_tsquery('english', $1)), 0), tasks.id"
+            }
+            Sort::TimeAsc => "tasks.expected_time, tasks.id",
+            Sort::TimeDesc => "-tasks.expected_time, tasks.id",
+            Sort::ComplexityAsc => "tasks.complexity, tasks.id",
+            Sort::ComplexityDesc => "-tasks.complexity, tasks.id",
+        }
+    }
+
+    // the whole key runs backwards, so the index on created_at serves the newest first
+    fn descending(&self) -> bool {
+        matches!(self, Sort::Newest)
+    }
+
+    fn order(&self) -> String {
+        if self.descending() {
+            self.key().replace(',', " DESC,") + " DESC"
+        } else {
+            self.key().to_owned()
+        }
+    }
+
+    // a deleted cursor row has no key to compare with, then the page goes on by id
+    fn after(&self, cursor: &str) -> String {
+        let key = self.key();
+        let op = if self.descending() { "<" } else { ">" };
+        format!(
+            "({cursor}::int IS NULL OR ({key}) {op} (SELECT {key} FROM tasks WHERE tasks.id = {cursor})
+            OR (NOT EXISTS (SELECT 1 FROM tasks WHERE tasks.id = {cursor}) AND tasks.id {op} {cursor}))"
+        )
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Urgency {
+    #[default]
+    Any,
+    Overdue,
+    DueSoon,
+    NoDeadline,
+}
+
+impl Urgency {
+    fn condition(&self) -> &'static str {
+        match self {
+            Urgency::Any => "true",
+            Urgency::Overdue => "due_at < now()",
+            Urgency::DueSoon => "due_at BETWEEN now() AND now() + interval '3 days'",
+            Urgency::NoDeadline => "due_at IS NULL",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TagMatch {
+    #[default]
+    All,
+    Any,
+}
+
+impl TagMatch {
+    fn condition(&self) -> &'static str {
+        match self {
+            // a parent tag also matches its descendants
+            TagMatch::All => {
+                "($2::text[] IS NULL OR NOT EXISTS (SELECT 1 FROM unnest($2::text[]) r WHERE NOT tasks.tags && tag_subtree(r)))"
+            }
+            TagMatch::Any => {
+                "($2::text[] IS NULL OR tasks.tags && ARRAY(SELECT unnest(tag_subtree(r)) FROM unnest($2::text[]) r))"
+            }
+        }
+    }
+}
+
+pub const BOARD_PAGE_SIZE: i64 = 30;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct BoardFilter {
+    #[serde(default)]
+    pub sort: Sort,
+    #[serde(default)]
+    pub urgency: Urgency,
+    /// Full-text query over descriptions, websearch syntax.
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub q: Option<String>,
+    /// Space separated tags.
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub tags: Option<String>,
+    #[serde(default)]
+    pub tag_match: TagMatch,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub complexity_min: Option<i16>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub complexity_max: Option<i16>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub time_min: Option<f32>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub time_max: Option<f32>,
+}
+
+impl BoardFilter {
+    fn tags(&self) -> Option<Vec<&str>> {
+        self.tags.as_ref().map(|t| t.split_whitespace().collect())
+    }
+
+    fn sort(&self) -> Sort {
+        // there is nothing to rank without a query
+        match (self.sort, &self.q) {
+            (Sort::Relevance, None) => Sort::Id,
+            (sort, _) => sort,
+        }
+    }
 }
 
+#[instrument(level = "debug", skip(db_client, task))]
 pub async fn create(
     db_client: &DbClient<'_>,
     task: &TaskCreateData,
@@ -30,20 +233,25 @@ pub async fn create(
     let c: i16 = task.complexity.into();
     let row = db_client
         .query_one(
-            "INSERT INTO tasks (complexity, expected_time, tags, description) VALUES ($1, $2, $3, $4) returning *",
-            &[&c, &task.expected_time, &task.tags, &task.description],
+            &format!("INSERT INTO tasks (complexity, expected_time, tags, description, due_at, campaign_id, parent_id) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING {TASK_COLUMNS}"),
+            &[&c, &task.expected_time, &task.tags, &task.description, &task.due_at, &task.campaign_id, &task.parent_id],
         )
         .await?;
-    Ok(Task {
-        id: row.get("id"),
-        complexity: row.get::<&str, i16>("complexity").into(),
-        description: row.get("description"),
-        expected_time: row.get("expected_time"),
-        tags: row.get("tags"),
-        assigned_to: row.get("assigned_to"),
-    })
+    Ok(row.into())
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get(db_client: &DbClient<'_>, task_id: i32) -> Result<Task, tokio_postgres::Error> {
+    Ok(db_client
+        .query_one(
+            &format!("SELECT {TASK_COLUMNS} FROM tasks WHERE id = $1"),
+            &[&task_id],
+        )
+        .await?
+        .into())
 }
 
+#[instrument(level = "debug", skip(db_client))]
 pub async fn assigned_to(
     db_client: &DbClient<'_>,
     task_id: i32,
@@ -57,92 +265,138 @@ pub async fn assigned_to(
     }
 }
 
+#[instrument(level = "debug", skip(db_client))]
 pub async fn assign_to(
     db_client: &DbClient<'_>,
     task_id: i32,
     user_id: Option<i32>,
 ) -> Result<u64, tokio_postgres::Error> {
+    // stop the timer of the previous assignee
+    if user_id.is_none() {
+        worklog::stop_all(db_client, task_id).await?;
+    }
     db_client
         .execute(
-            "UPDATE tasks SET assigned_to = $1 WHERE id = $2",
+            "UPDATE tasks SET assigned_to = $1, assigned_at = CASE WHEN $1::int IS NULL THEN NULL ELSE now() END, overdue_at = NULL WHERE id = $2",
             &[&user_id, &task_id],
         )
         .await
 }
 
+#[instrument(level = "debug", skip(db_client))]
+pub async fn set_campaign(
+    db_client: &DbClient<'_>,
+    task_id: i32,
+    campaign_id: Option<i32>,
+) -> Result<u64, tokio_postgres::Error> {
+    db_client
+        .execute(
+            "UPDATE tasks SET campaign_id = $1 WHERE id = $2",
+            &[&campaign_id, &task_id],
+        )
+        .await
+}
+
+/// False when the quest is still blocked or has unfinished subquests.
+#[instrument(level = "debug", skip(db_client))]
 pub async fn complete(
     db_client: &DbClient<'_>,
     task_id: i32,
     user: User,
-) -> Result<u64, tokio_postgres::Error> {
-    let u_tags: HashSet<Box<str>, RandomState> = HashSet::from_iter(user.tags.into_iter());
-    let t_tags: HashSet<Box<str>, RandomState> =
-        HashSet::from_iter(get_tags(&db_client, task_id).await?.into_iter());
-    let extra_tags = t_tags.difference(&u_tags).cloned().collect();
-    let (q1, q2, q3) = join!(
-        // add tags
-        async { user::add_tags(&db_client, user.id, extra_tags).await },
+    rules: &Rules,
+) -> Result<bool, tokio_postgres::Error> {
+    worklog::stop_all(db_client, task_id).await?;
+    // skills grow from completed_tasks, see entities::skill
+    let (q1, _) = join!(
         // mark task as completed
         async {
             db_client
                 .execute(
-                    "INSERT INTO completed_tasks (user_id, task_id) VALUES ($1, $2)",
-                    &[&user.id, &task_id],
+                    "INSERT INTO completed_tasks (user_id, task_id, xp)
+                        SELECT $1, id, ROUND(quest_xp(complexity, expected_time) * CASE
+                            WHEN due_at IS NULL THEN 1
+                            WHEN due_at < now() THEN $3::real
+                            WHEN due_at > now() + interval '1 day' THEN $4::real
+                            ELSE 1
+                        END) FROM tasks WHERE id = $2
+                        -- blockers and subquests are checked again, the caller may have raced
+                        AND NOT EXISTS (SELECT 1 FROM task_dependencies d WHERE d.task_id = tasks.id
+                            AND NOT EXISTS (SELECT 1 FROM completed_tasks WHERE task_id = d.blocked_by))
+                        AND NOT EXISTS (SELECT 1 FROM tasks c WHERE c.parent_id = tasks.id
+                            AND NOT EXISTS (SELECT 1 FROM completed_tasks WHERE task_id = c.id))",
+                    &[&user.id, &task_id, &rules.late_penalty, &rules.early_bonus],
                 )
                 .await
         },
         // calibrate class
         async {
-            user::calibrate_class(&db_client, user.id, user.class)
+            user::calibrate_class(db_client, user.id, user.class, rules.calibration_interval)
                 .await
                 .unwrap()
         }
     );
-    Ok(q1? + q2? + q3)
+    Ok(q1? > 0)
 }
 
+#[instrument(level = "debug", skip(db_client))]
 pub async fn delete(db_client: &DbClient<'_>, task_id: i32) -> Result<u64, tokio_postgres::Error> {
     db_client
         .execute("DELETE FROM tasks WHERE id = $1", &[&task_id])
         .await
 }
 
+pub const ASSIGNED_PAGE_SIZE: i64 = 10;
+
+#[instrument(level = "debug", skip(db_client))]
 pub async fn get_assigned(
     db_client: &DbClient<'_>,
     user_id: i32,
-) -> Result<Vec<Task>, tokio_postgres::Error> {
-    Ok(db_client
-        .query("SELECT * from tasks WHERE assigned_to = $1 AND NOT EXISTS(SELECT * FROM completed_tasks WHERE task_id = tasks.id)", &[&user_id])
+    cursor: &Cursor,
+) -> Result<Page<Task>, tokio_postgres::Error> {
+    let order = Sort::Urgency.order();
+    let after = Sort::Urgency.after("$2");
+    let limit = cursor.limit(ASSIGNED_PAGE_SIZE);
+    let tasks = db_client
+        .query(&format!("SELECT {TASK_COLUMNS} from tasks WHERE assigned_to = $1 AND NOT EXISTS(SELECT * FROM completed_tasks WHERE task_id = tasks.id)
+            AND {after}
+            ORDER BY {order} LIMIT $3"), &[&user_id, &cursor.after, &(limit + 1)])
         .await?
         .into_iter()
-        .map(|row| Task {
-            id: row.get("id"),
-            complexity: row.get::<&str, i16>("complexity").into(),
-            description: row.get("description"),
-            expected_time: row.get("expected_time"),
-            tags: row.get("tags"),
-            assigned_to: row.get("assigned_to"),
-        })
-        .collect())
+        .map(Task::from)
+        .collect();
+
+    Ok(Page::new(tasks, limit, |t| t.id))
 }
 
-pub async fn get_available(db_client: &DbClient<'_>) -> Result<Vec<Task>, tokio_postgres::Error> {
-    Ok(db_client
-        .query("SELECT * from tasks WHERE assigned_to is NULL AND NOT EXISTS (SELECT 1 FROM completed_tasks WHERE task_id = tasks.id)", &[])
-        .await
-        .unwrap()
+/// One page of tasks that can be taken, narrowed by the board filter.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_available(
+    db_client: &DbClient<'_>,
+    filter: &BoardFilter,
+    cursor: &Cursor,
+) -> Result<Page<Task>, tokio_postgres::Error> {
+    let order = filter.sort().order();
+    let after = filter.sort().after("$7");
+    let limit = cursor.limit(BOARD_PAGE_SIZE);
+    let tasks = db_client
+        .query(&format!("SELECT {TASK_COLUMNS} from tasks WHERE assigned_to is NULL AND NOT EXISTS (SELECT 1 FROM completed_tasks WHERE task_id = tasks.id)
+            AND NOT EXISTS (SELECT 1 FROM task_dependencies d WHERE d.task_id = tasks.id AND NOT EXISTS (SELECT 1 FROM completed_tasks WHERE task_id = d.blocked_by))
+            AND ($1::text IS NULL OR tasks.search @@ (websearch_to_tsquery('russian', $1) || websearch_to_tsquery('english', $1)))
+            AND {} AND {}
+            AND ($3::smallint IS NULL OR complexity >= $3) AND ($4::smallint IS NULL OR complexity <= $4)
+            AND ($5::real IS NULL OR expected_time >= $5) AND ($6::real IS NULL OR expected_time <= $6)
+            AND {after}
+            ORDER BY {order} LIMIT $8", filter.tag_match.condition(), filter.urgency.condition()),
+            &[&filter.q, &filter.tags(), &filter.complexity_min, &filter.complexity_max, &filter.time_min, &filter.time_max, &cursor.after, &(limit + 1)])
+        .await?
         .into_iter()
-        .map(|row| Task {
-            id: row.get("id"),
-            description: row.get("description"),
-            complexity: row.get::<&str, i16>("complexity").into(),
-            expected_time: row.get("expected_time"),
-            tags: row.get("tags"),
-            assigned_to: row.get("assigned_to"),
-        })
-        .collect())
+        .map(Task::from)
+        .collect();
+
+    Ok(Page::new(tasks, limit, |t| t.id))
 }
 
+#[instrument(level = "debug", skip(db_client))]
 pub async fn get_count(
     db_client: &DbClient<'_>,
     user_id: i32,
@@ -156,13 +410,14 @@ pub async fn get_count(
         .get("count"))
 }
 
+#[instrument(level = "debug", skip(db_client))]
 pub async fn get_avg_complexity(
     db_client: &DbClient<'_>,
     user_id: i32,
 ) -> Result<f32, tokio_postgres::Error> {
     Ok(db_client
         .query_one(
-            "SELECT CAST(AVG(complexity) AS REAL) FROM completed_tasks JOIN tasks ON user_id = $1",
+            "SELECT CAST(AVG(complexity) AS REAL) FROM completed_tasks JOIN tasks ON tasks.id = task_id WHERE user_id = $1",
             &[&user_id],
         )
         .await?
@@ -170,13 +425,22 @@ pub async fn get_avg_complexity(
         .unwrap_or(0.0))
 }
 
+/// Average real duration of the user's completed tasks in hours.
+/// Tasks completed without any worklog fall back to their estimate.
+#[instrument(level = "debug", skip(db_client))]
 pub async fn get_avg_duration(
     db_client: &DbClient<'_>,
     user_id: i32,
 ) -> Result<f32, tokio_postgres::Error> {
     Ok(db_client
         .query_one(
-            "SELECT CAST(AVG(expected_time) AS REAL) FROM completed_tasks JOIN tasks ON user_id = $1",
+            "SELECT CAST(AVG(COALESCE(w.spent, tasks.expected_time)) AS REAL) FROM completed_tasks
+                JOIN tasks ON tasks.id = completed_tasks.task_id
+                LEFT JOIN (
+                    SELECT task_id, SUM(EXTRACT(EPOCH FROM (stopped_at - started_at))) / 3600 AS spent
+                    FROM worklogs WHERE user_id = $1 AND stopped_at IS NOT NULL GROUP BY task_id
+                ) w ON w.task_id = completed_tasks.task_id
+                WHERE completed_tasks.user_id = $1",
             &[&user_id],
         )
         .await?
@@ -184,12 +448,40 @@ pub async fn get_avg_duration(
         .unwrap_or(0.0))
 }
 
-pub async fn get_tags(
+/// Marks in-progress tasks that passed their due date.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn flag_overdue(db_client: &DbClient<'_>) -> Result<u64, tokio_postgres::Error> {
+    db_client
+        .execute(
+            "UPDATE tasks SET overdue_at = now() WHERE due_at < now() AND overdue_at IS NULL AND assigned_to IS NOT NULL
+                AND NOT EXISTS (SELECT 1 FROM completed_tasks WHERE task_id = tasks.id)",
+            &[],
+        )
+        .await
+}
+
+/// Returns tasks flagged as overdue for longer than `grace_hours` to the quest board.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn return_overdue(
     db_client: &DbClient<'_>,
-    task_id: i32,
-) -> Result<Vec<Box<str>>, tokio_postgres::Error> {
-    Ok(db_client
-        .query_one("SELECT tags FROM tasks WHERE id = $1", &[&task_id])
+    grace_hours: f64,
+) -> Result<u64, tokio_postgres::Error> {
+    let returned: i64 = db_client
+        .query_one(
+            "WITH returned AS (
+                UPDATE tasks SET assigned_to = NULL, assigned_at = NULL, overdue_at = NULL
+                WHERE overdue_at < now() - make_interval(secs => $1 * 3600)
+                    AND NOT EXISTS (SELECT 1 FROM completed_tasks WHERE task_id = tasks.id)
+                RETURNING id
+            ), stopped AS (
+                UPDATE worklogs SET stopped_at = now() FROM returned
+                WHERE worklogs.task_id = returned.id AND stopped_at IS NULL
+            )
+            SELECT COUNT(*) FROM returned",
+            &[&grace_hours],
+        )
         .await?
-        .get("tags"))
+        .get("count");
+
+    Ok(returned as u64)
 }
@@ -0,0 +1,476 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use tokio_postgres::Row;
+
+use super::{
+    tag::MAX_TAG_LEN,
+    task::{Task, TASK_COLUMNS},
+    user::Class,
+};
+use crate::libs::{csv, db::DbClient};
+use tracing::instrument;
+
+pub const MAX_IMPORT_ROWS: usize = 1000;
+const MAX_DESCRIPTION_LEN: usize = 1000;
+const COLUMNS: [&str; 4] = ["description", "tags", "complexity", "expected_time"];
+
+/// A quest to be created, the fields of `TaskCreateForm` that a backlog usually has.
+#[derive(Serialize)]
+pub struct Quest {
+    pub description: Box<str>,
+    pub tags: Vec<Box<str>>,
+    pub complexity: Class,
+    pub expected_time: f32,
+}
+
+/// Rows are counted from one, the CSV header is not a row.
+#[derive(Serialize)]
+pub struct RowError {
+    pub row: usize,
+    pub message: Box<str>,
+}
+
+#[derive(Default)]
+pub struct Parsed {
+    pub quests: Vec<Quest>,
+    pub errors: Vec<RowError>,
+}
+
+// every field as it came, still text
+#[derive(Default)]
+struct RawQuest {
+    description: Option<String>,
+    tags: Option<String>,
+    complexity: Option<String>,
+    expected_time: Option<String>,
+}
+
+impl RawQuest {
+    fn set(&mut self, column: &str, value: String) {
+        let value = Some(value).filter(|v| !v.trim().is_empty());
+        match column {
+            "description" => self.description = value,
+            "tags" => self.tags = value,
+            "complexity" => self.complexity = value,
+            "expected_time" => self.expected_time = value,
+            _ => {}
+        }
+    }
+
+    fn validate(self) -> Result<Quest, Vec<String>> {
+        let mut errors = Vec::new();
+
+        let description = self.description.unwrap_or_default().trim().to_owned();
+        if description.is_empty() {
+            errors.push("Нет описания".to_owned());
+        } else if description.chars().count() > MAX_DESCRIPTION_LEN {
+            errors.push(format!("Описание длиннее {MAX_DESCRIPTION_LEN} символов"));
+        }
+
+        let tags: Vec<Box<str>> = self
+            .tags
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(Box::from)
+            .collect();
+        if tags.is_empty() {
+            errors.push("Заданию нужен хотя бы один тэг".to_owned());
+        }
+        for t in tags.iter().filter(|t| t.chars().count() > MAX_TAG_LEN) {
+            errors.push(format!("Тэг {t} длиннее {MAX_TAG_LEN} символов"));
+        }
+
+        // a letter as on the board or the number the form sends
+        let complexity = self.complexity.unwrap_or_default();
+        let complexity = Class::from_str(&complexity.trim().to_uppercase())
+            .ok()
+            .or_else(|| {
+                complexity
+                    .trim()
+                    .parse::<i16>()
+                    .ok()
+                    .filter(|c| (0..=2).contains(c))
+                    .map(Class::from)
+            });
+        if complexity.is_none() {
+            errors.push("Сложность должна быть A, B, C или от 0 до 2".to_owned());
+        }
+
+        let expected_time = self
+            .expected_time
+            .unwrap_or_default()
+            .trim()
+            .replace(',', ".")
+            .parse::<f32>()
+            .ok()
+            .filter(|t| t.is_finite() && *t > 0.0);
+        if expected_time.is_none() {
+            errors.push("Ожидаемое время должно быть положительным числом часов".to_owned());
+        }
+
+        match (complexity, expected_time) {
+            (Some(complexity), Some(expected_time)) if errors.is_empty() => Ok(Quest {
+                description: description.into(),
+                tags,
+                complexity,
+                expected_time,
+            }),
+            _ => Err(errors),
+        }
+    }
+}
+
+/// Reads quests from a JSON array of objects or from CSV with a header.
+/// The error is about the whole file, the problems of single rows are in `Parsed::errors`.
+pub fn parse(input: &str) -> Result<Parsed, &'static str> {
+    let raw = if input.trim_start().starts_with(['[', '{']) {
+        parse_json(input)?
+    } else {
+        parse_csv(input)?
+    };
+    if raw.is_empty() {
+        return Err("В свитке нет ни одного задания");
+    }
+    if raw.len() > MAX_IMPORT_ROWS {
+        return Err("Слишком много заданий за раз");
+    }
+
+    let mut parsed = Parsed::default();
+    for (i, r) in raw.into_iter().enumerate() {
+        match r.and_then(RawQuest::validate) {
+            Ok(q) => parsed.quests.push(q),
+            Err(messages) => parsed.errors.extend(messages.into_iter().map(|m| RowError {
+                row: i + 1,
+                message: m.into(),
+            })),
+        }
+    }
+    Ok(parsed)
+}
+
+fn parse_csv(input: &str) -> Result<Vec<Result<RawQuest, Vec<String>>>, &'static str> {
+    let mut records = csv::read_records(input).into_iter();
+    let header: Vec<String> = records
+        .next()
+        .ok_or("Свиток пуст")?
+        .into_iter()
+        .map(|c| c.trim().to_lowercase())
+        .collect();
+    if !COLUMNS.iter().all(|c| header.iter().any(|h| h == c)) {
+        return Err("В заголовке нужны столбцы description, tags, complexity и expected_time");
+    }
+
+    Ok(records
+        .map(|record| {
+            let mut q = RawQuest::default();
+            for (column, value) in header.iter().zip(record) {
+                q.set(column, value);
+            }
+            Ok(q)
+        })
+        .collect())
+}
+
+fn parse_json(input: &str) -> Result<Vec<Result<RawQuest, Vec<String>>>, &'static str> {
+    let rows: Vec<Value> =
+        serde_json::from_str(input).map_err(|_| "Свиток не читается как массив JSON")?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let Value::Object(fields) = row else {
+                return Err(vec!["Задание должно быть объектом".to_owned()]);
+            };
+            let mut q = RawQuest::default();
+            for (column, value) in fields {
+                // tags may come as a list or as one string, like in the form
+                let value = match value {
+                    Value::Null => continue,
+                    Value::String(s) => s,
+                    Value::Array(items) => items
+                        .iter()
+                        .map(|v| match v {
+                            Value::String(s) => s.clone(),
+                            v => v.to_string(),
+                        })
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    v => v.to_string(),
+                };
+                q.set(&column, value);
+            }
+            Ok(q)
+        })
+        .collect())
+}
+
+/// The quests with tags as the guild would store them, nothing is written.
+/// Tags new to the catalog keep the spelling of the file.
+#[instrument(level = "debug", skip(db_client, quests))]
+pub async fn preview(
+    db_client: &DbClient<'_>,
+    quests: Vec<Quest>,
+) -> Result<Vec<Quest>, tokio_postgres::Error> {
+    let tags: Vec<String> = quests.iter().map(|q| q.tags.join(" ")).collect();
+    let rows = db_client
+        .query(
+            "SELECT normalize_tags(string_to_array(q.tags, ' ')) AS tags
+            FROM unnest($1::text[]) WITH ORDINALITY AS q(tags, ord)
+            ORDER BY q.ord",
+            &[&tags],
+        )
+        .await?;
+
+    Ok(quests
+        .into_iter()
+        .zip(rows)
+        .map(|(q, row)| Quest {
+            tags: row.get("tags"),
+            ..q
+        })
+        .collect())
+}
+
+/// Creates the quests in one transaction, none of them is kept unless all are.
+#[instrument(level = "debug", skip(db_client, quests))]
+pub async fn import(
+    db_client: &mut DbClient<'_>,
+    quests: &[Quest],
+) -> Result<Vec<Task>, tokio_postgres::Error> {
+    let all_tags: Vec<&str> = quests
+        .iter()
+        .flat_map(|q| q.tags.iter().map(|t| &**t))
+        .collect();
+    let complexities: Vec<i16> = quests.iter().map(|q| q.complexity.into()).collect();
+    let expected_times: Vec<f32> = quests.iter().map(|q| q.expected_time).collect();
+    // tags have no spaces, so each quest passes them as one string
+    let tags: Vec<String> = quests.iter().map(|q| q.tags.join(" ")).collect();
+    let descriptions: Vec<&str> = quests.iter().map(|q| &*q.description).collect();
+
+    let tx = db_client.transaction().await?;
+    tx.execute(
+        "INSERT INTO tags (name) SELECT DISTINCT ON (lower(t)) t FROM unnest($1::text[]) t
+            WHERE NOT EXISTS (SELECT 1 FROM tag_synonyms WHERE synonym = lower(t))
+            ORDER BY lower(t), t
+            ON CONFLICT DO NOTHING",
+        &[&all_tags],
+    )
+    .await?;
+    let mut tasks: Vec<Task> = tx
+        .query(
+            &format!(
+                "INSERT INTO tasks (complexity, expected_time, tags, description)
+                SELECT q.complexity, q.expected_time, normalize_tags(string_to_array(q.tags, ' ')), q.description
+                FROM unnest($1::int2[], $2::real[], $3::text[], $4::text[])
+                    WITH ORDINALITY AS q(complexity, expected_time, tags, description, ord)
+                ORDER BY q.ord
+                RETURNING {TASK_COLUMNS}"
+            ),
+            &[&complexities, &expected_times, &tags, &descriptions],
+        )
+        .await?
+        .into_iter()
+        .map(Task::from)
+        .collect();
+    tasks.sort_by_key(|t| t.id);
+    tx.commit().await?;
+
+    Ok(tasks)
+}
+
+#[derive(Serialize)]
+pub struct Completion {
+    pub id: i32,
+    pub task_id: i32,
+    pub user_id: Option<i32>,
+    pub login: Option<Box<str>>,
+    pub xp: Option<i32>,
+    /// Unknown for completions older than the column.
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl From<Row> for Completion {
+    fn from(row: Row) -> Self {
+        Completion {
+            id: row.get("id"),
+            task_id: row.get("task_id"),
+            user_id: row.get("user_id"),
+            login: row.get("login"),
+            xp: row.get("xp"),
+            completed_at: row.get("completed_at"),
+        }
+    }
+}
+
+/// A user without the password hash, with what they have achieved.
+#[derive(Serialize)]
+pub struct Member {
+    pub id: i32,
+    pub login: Box<str>,
+    pub name: Box<str>,
+    pub class: Class,
+    pub is_admin: bool,
+    pub completed: i64,
+    pub xp: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<Row> for Member {
+    fn from(row: Row) -> Self {
+        Member {
+            id: row.get("id"),
+            login: row.get("login"),
+            name: row.get("name"),
+            class: row.get::<&str, i16>("class").into(),
+            is_admin: row.get("is_admin"),
+            completed: row.get("completed"),
+            xp: row.get("xp"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+        }
+    }
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_tasks(db_client: &DbClient<'_>) -> Result<Vec<Task>, tokio_postgres::Error> {
+    Ok(db_client
+        .query(
+            &format!("SELECT {TASK_COLUMNS} FROM tasks ORDER BY tasks.id"),
+            &[],
+        )
+        .await?
+        .into_iter()
+        .map(Task::from)
+        .collect())
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_completions(
+    db_client: &DbClient<'_>,
+) -> Result<Vec<Completion>, tokio_postgres::Error> {
+    Ok(db_client
+        .query(
+            "SELECT ct.id, ct.task_id, ct.user_id, u.login, ct.xp, ct.completed_at
+            FROM completed_tasks ct LEFT JOIN users u ON u.id = ct.user_id
+            ORDER BY ct.id",
+            &[],
+        )
+        .await?
+        .into_iter()
+        .map(Completion::from)
+        .collect())
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_members(db_client: &DbClient<'_>) -> Result<Vec<Member>, tokio_postgres::Error> {
+    Ok(db_client
+        .query(
+            "SELECT u.id, u.login, u.name, u.class, u.is_admin,
+                u.created_at, u.updated_at,
+                COUNT(ct.id) AS completed, COALESCE(SUM(ct.xp), 0)::int8 AS xp
+            FROM users u LEFT JOIN completed_tasks ct ON ct.user_id = u.id
+            GROUP BY u.id ORDER BY u.id",
+            &[],
+        )
+        .await?
+        .into_iter()
+        .map(Member::from)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(parsed: &Parsed) -> Vec<(usize, &str)> {
+        parsed.errors.iter().map(|e| (e.row, &*e.message)).collect()
+    }
+
+    #[test]
+    fn csv_with_any_column_order() {
+        let parsed = parse(
+            "\u{feff}Tags,expected_time,Description,complexity,extra\r\n\
+            rust web,\"1,5\",\"Fix the \"\"login\"\" page, again\",a,ignored\r\n\
+            \r\n\
+            docs,2,\"=two\nlines\",1,\r\n",
+        )
+        .unwrap();
+
+        assert!(parsed.errors.is_empty());
+        let [first, second] = &parsed.quests[..] else {
+            panic!("expected two quests");
+        };
+        assert_eq!(&*first.description, "Fix the \"login\" page, again");
+        assert_eq!(
+            first.tags,
+            vec!["rust".into(), "web".into()] as Vec<Box<str>>
+        );
+        assert_eq!(first.complexity, Class::A);
+        assert_eq!(first.expected_time, 1.5);
+        // the quote before a formula is dropped again
+        assert_eq!(&*second.description, "=two\nlines");
+        assert_eq!(second.complexity, Class::B);
+    }
+
+    #[test]
+    fn csv_needs_the_columns() {
+        assert!(parse("description,tags\nx,y").is_err());
+        assert!(parse("\n\n").is_err());
+        assert!(parse("description,tags,complexity,expected_time\n").is_err());
+    }
+
+    #[test]
+    fn json_rows() {
+        let parsed = parse(
+            r#"[
+                {"description": "From JSON", "tags": ["json", "import"], "complexity": 2, "expected_time": 3},
+                {"description": "One string", "tags": "a b", "complexity": "C", "expected_time": "0,5"}
+            ]"#,
+        )
+        .unwrap();
+
+        assert!(parsed.errors.is_empty());
+        assert_eq!(parsed.quests.len(), 2);
+        assert_eq!(parsed.quests[0].complexity, Class::A);
+        assert_eq!(parsed.quests[0].tags.len(), 2);
+        assert_eq!(parsed.quests[1].expected_time, 0.5);
+        assert!(parse("{\"description\": \"not an array\"}").is_err());
+        assert!(parse("[]").is_err());
+    }
+
+    #[test]
+    fn errors_name_their_rows() {
+        let long_tag = "x".repeat(MAX_TAG_LEN + 1);
+        let parsed = parse(&format!(
+            "description,tags,complexity,expected_time\n\
+            ok,t,C,1\n\
+            ,,D,0\n\
+            ok,{long_tag},B,-1\n"
+        ))
+        .unwrap();
+
+        assert_eq!(parsed.quests.len(), 1);
+        let errors = messages(&parsed);
+        assert_eq!(errors.iter().filter(|(row, _)| *row == 2).count(), 4);
+        assert_eq!(errors.iter().filter(|(row, _)| *row == 3).count(), 2);
+        assert!(errors.iter().all(|(row, _)| *row != 1));
+
+        let parsed =
+            parse(r#"[1, {"description": "x", "tags": "t", "complexity": 0, "expected_time": 1}]"#)
+                .unwrap();
+        assert_eq!(messages(&parsed), vec![(1, "Задание должно быть объектом")]);
+    }
+
+    #[test]
+    fn too_many_rows() {
+        let mut input = "description,tags,complexity,expected_time\n".to_owned();
+        for _ in 0..=MAX_IMPORT_ROWS {
+            input.push_str("x,t,C,1\n");
+        }
+        assert!(parse(&input).is_err());
+    }
+}
@@ -1,14 +1,20 @@
-use std::fmt::{self, Display};
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
 
+use chrono::{DateTime, Utc};
 use serde::{
     de::{self, Visitor},
     Deserialize, Serialize, Serializer,
 };
 use tokio::join;
+use tokio_postgres::Row;
 
 use crate::libs::db::DbClient;
 
 use super::task;
+use tracing::instrument;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Class {
@@ -41,6 +47,19 @@ impl Serialize for Class {
     }
 }
 
+impl FromStr for Class {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "C" => Ok(Class::C),
+            "B" => Ok(Class::B),
+            "A" => Ok(Class::A),
+            _ => Err(()),
+        }
+    }
+}
+
 struct ClassVisitor;
 
 impl<'de> Visitor<'de> for ClassVisitor {
@@ -50,11 +69,11 @@ impl<'de> Visitor<'de> for ClassVisitor {
         formatter.write_str("an integer between 0 and 3")
     }
 
-    fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        match v.as_str() {
+        match v {
             "C" => Ok(Class::C),
             "B" => Ok(Class::B),
             "A" => Ok(Class::A),
@@ -83,9 +102,9 @@ impl From<i16> for Class {
     }
 }
 
-impl Into<i16> for Class {
-    fn into(self) -> i16 {
-        match self {
+impl From<Class> for i16 {
+    fn from(value: Class) -> Self {
+        match value {
             Class::C => 0,
             Class::B => 1,
             Class::A => 2,
@@ -102,25 +121,37 @@ pub struct User {
     pub class: Class,
     pub is_admin: bool,
     pub tags: Vec<Box<str>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<Row> for User {
+    fn from(u: Row) -> Self {
+        User {
+            id: u.get("id"),
+            login: u.get("login"),
+            name: u.get("name"),
+            pw_hash: u.get("password"),
+            class: u.get::<&str, i16>("class").into(),
+            is_admin: u.get("is_admin"),
+            tags: u.get("tags"),
+            created_at: u.get("created_at"),
+            updated_at: u.get("updated_at"),
+        }
+    }
 }
 
+#[instrument(level = "debug", skip(db_client, user))]
 pub async fn create(db_client: &DbClient<'_>, user: User) -> Result<User, tokio_postgres::Error> {
     let u = db_client.query_one(
         "INSERT INTO users (login, name, password, class, is_admin) VALUES ($1, $2, $3, 0, false) RETURNING *",
         &[&user.login, &user.name, &user.pw_hash],
     ).await?;
 
-    Ok(User {
-        id: u.get("id"),
-        login: u.get("login"),
-        name: u.get("name"),
-        pw_hash: u.get("password"),
-        class: u.get::<&str, i16>("class").into(),
-        is_admin: u.get("is_admin"),
-        tags: u.get("tags"),
-    })
+    Ok(u.into())
 }
 
+#[instrument(level = "debug", skip(db_client))]
 pub async fn exists(db_client: &DbClient<'_>, login: &str) -> Result<bool, tokio_postgres::Error> {
     let row = db_client
         .query_one("SELECT COUNT(*) FROM users WHERE login = $1", &[&login])
@@ -129,22 +160,16 @@ pub async fn exists(db_client: &DbClient<'_>, login: &str) -> Result<bool, tokio
     Ok(row.get::<&str, i64>("count") == 1)
 }
 
+#[instrument(level = "debug", skip(db_client))]
 pub async fn get(db_client: &DbClient<'_>, id: i32) -> Result<User, tokio_postgres::Error> {
     let u = db_client
         .query_one("SELECT * FROM users where id = $1", &[&id])
         .await?;
 
-    Ok(User {
-        id: u.get("id"),
-        login: u.get("login"),
-        name: u.get("name"),
-        pw_hash: u.get("password"),
-        class: u.get::<&str, i16>("class").into(),
-        is_admin: u.get("is_admin"),
-        tags: u.get("tags"),
-    })
+    Ok(u.into())
 }
 
+#[instrument(level = "debug", skip(db_client))]
 pub async fn get_by_login(
     db_client: &DbClient<'_>,
     login: &str,
@@ -153,60 +178,22 @@ pub async fn get_by_login(
         .query_one("SELECT * FROM users where login = $1", &[&login])
         .await?;
 
-    Ok(User {
-        id: u.get("id"),
-        login: u.get("login"),
-        name: u.get("name"),
-        pw_hash: u.get("password"),
-        class: u.get::<&str, i16>("class").into(),
-        is_admin: u.get("is_admin"),
-        tags: u.get("tags"),
-    })
-}
-
-pub async fn top_players(db_client: &DbClient<'_>) -> Result<Vec<User>, tokio_postgres::Error> {
-    let users = db_client.query("SELECT * FROM users ORDER BY (SELECT COUNT(*) FROM completed_tasks WHERE user_id = users.id) DESC LIMIT 10", &[]).await.unwrap().into_iter().map(|row| User {
-        id: row.get("id"),
-        login: row.get("login"),
-        name: row.get("name"),
-        pw_hash: row.get("password"),
-        class: row.get::<&str, i16>("class").into(),
-        is_admin: row.get("is_admin"),
-        tags: row.get("tags"),
-    }).collect();
-
-    Ok(users)
-}
-
-pub async fn top_players_by_class(
-    db_client: &DbClient<'_>,
-    class: Class,
-) -> Result<Vec<User>, tokio_postgres::Error> {
-    let c: i16 = class.into();
-    let users = db_client.query("SELECT * FROM users WHERE class = $1 ORDER BY (SELECT COUNT(*) FROM completed_tasks WHERE user_id = users.id) DESC LIMIT 10", &[&c]).await.unwrap().into_iter().map(|row| User {
-        id: row.get("id"),
-        login: row.get("login"),
-        name: row.get("name"),
-        pw_hash: row.get("password"),
-        class: row.get::<&str, i16>("class").into(),
-        is_admin: row.get("is_admin"),
-        tags: row.get("tags"),
-    }).collect();
-
-    Ok(users)
+    Ok(u.into())
 }
 
+#[instrument(level = "debug", skip(db_client))]
 pub async fn calibrate_class(
     db_client: &DbClient<'_>,
     user_id: i32,
     user_class: Class,
+    interval: i64,
 ) -> Result<u64, tokio_postgres::Error> {
     let (total, complexity) = join!(
         task::get_count(db_client, user_id),
         task::get_avg_complexity(db_client, user_id)
     );
 
-    if total? % 10 != 0 {
+    if total? % interval != 0 {
         return Ok(0);
     }
 
@@ -218,6 +205,7 @@ pub async fn calibrate_class(
     Ok(0)
 }
 
+#[instrument(level = "debug", skip(db_client))]
 pub async fn set_class(
     db_client: &DbClient<'_>,
     id: i32,
@@ -228,16 +216,3 @@ pub async fn set_class(
         .execute("UPDATE users SET class = $1 WHERE id = $2", &[&class, &id])
         .await
 }
-
-pub async fn add_tags(
-    db_client: &DbClient<'_>,
-    id: i32,
-    tags: Vec<Box<str>>,
-) -> Result<u64, tokio_postgres::Error> {
-    db_client
-        .execute(
-            "UPDATE users SET tags = (tags || $1) WHERE id = $2",
-            &[&tags, &id],
-        )
-        .await
-}
@@ -0,0 +1,98 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::libs::db::DbClient;
+use tracing::instrument;
+
+#[derive(Serialize)]
+pub struct Worklog {
+    pub id: i32,
+    pub task_id: i32,
+    pub user_id: i32,
+    pub started_at: DateTime<Utc>,
+    pub stopped_at: Option<DateTime<Utc>>,
+    pub note: Option<Box<str>>,
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn start(
+    db_client: &DbClient<'_>,
+    task_id: i32,
+    user_id: i32,
+) -> Result<u64, tokio_postgres::Error> {
+    db_client
+        .execute(
+            "INSERT INTO worklogs (task_id, user_id, started_at) VALUES ($1, $2, now()) ON CONFLICT DO NOTHING",
+            &[&task_id, &user_id],
+        )
+        .await
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn pause(
+    db_client: &DbClient<'_>,
+    task_id: i32,
+    user_id: i32,
+) -> Result<u64, tokio_postgres::Error> {
+    db_client
+        .execute(
+            "UPDATE worklogs SET stopped_at = now() WHERE task_id = $1 AND user_id = $2 AND stopped_at IS NULL",
+            &[&task_id, &user_id],
+        )
+        .await
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn stop_all(
+    db_client: &DbClient<'_>,
+    task_id: i32,
+) -> Result<u64, tokio_postgres::Error> {
+    db_client
+        .execute(
+            "UPDATE worklogs SET stopped_at = now() WHERE task_id = $1 AND stopped_at IS NULL",
+            &[&task_id],
+        )
+        .await
+}
+
+/// Records time spent outside of the timer, ending now.
+#[instrument(level = "debug", skip(db_client))]
+pub async fn add(
+    db_client: &DbClient<'_>,
+    task_id: i32,
+    user_id: i32,
+    hours: f32,
+    note: Option<&str>,
+) -> Result<Worklog, tokio_postgres::Error> {
+    let row = db_client
+        .query_one(
+            "INSERT INTO worklogs (task_id, user_id, started_at, stopped_at, note)
+                VALUES ($1, $2, now() - make_interval(secs => $3::float8 * 3600), now(), $4) RETURNING *",
+            &[&task_id, &user_id, &(hours as f64), &note],
+        )
+        .await?;
+
+    Ok(Worklog {
+        id: row.get("id"),
+        task_id: row.get("task_id"),
+        user_id: row.get("user_id"),
+        started_at: row.get("started_at"),
+        stopped_at: row.get("stopped_at"),
+        note: row.get("note"),
+    })
+}
+
+#[instrument(level = "debug", skip(db_client))]
+pub async fn get_spent(
+    db_client: &DbClient<'_>,
+    task_id: i32,
+) -> Result<f32, tokio_postgres::Error> {
+    Ok(db_client
+        .query_one(
+            "SELECT CAST(COALESCE(SUM(EXTRACT(EPOCH FROM (COALESCE(stopped_at, now()) - started_at))), 0) / 3600 AS REAL) AS spent
+                FROM worklogs WHERE task_id = $1",
+            &[&task_id],
+        )
+        .await?
+        .get("spent"))
+}
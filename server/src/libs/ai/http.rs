@@ -0,0 +1,195 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_tuple::*;
+
+use super::{rerank, Error, Profile, Recommendation, Recommender};
+use crate::entities::{recommendation::Vote, task::Task};
+
+// Request:
+// {
+//  "version": 2,
+//  "top_n": 5,
+//  "worker": [2, 7, [["JavaScript", 4.5], ["React", 2], ["CSS", 1]]],
+//  "tasks": [
+//    [2, 7, ["Linux", "Docker"], 12],
+//    [1.5, 7, ["Kotlin", "Android Development"], 15]
+//  ],
+//  "feedback": {"up": [15], "down": [], "hide": [3]}
+// }
+// tags of the worker are weighted by proficiency, see entities::skill,
+// the last element of a task is its id. Hidden tasks are never sent,
+// votes are left to the service to re-rank by.
+//
+// Response: {"recommendations": [{"id": 15, "score": 0.87}, {"id": 12, "score": 0.61}]}
+//
+// Services speaking the first version answer with the task tuples themselves,
+// best first, with or without the id, and know nothing of votes:
+// their answers are re-ranked here.
+
+const PROTOCOL_VERSION: u32 = 2;
+
+#[derive(Serialize_tuple)]
+struct Params<'a> {
+    complexity: f32,
+    time: f32,
+    tags: &'a [Box<str>],
+    id: i32,
+}
+
+#[derive(Serialize_tuple)]
+struct WorkerParams<'a> {
+    complexity: f32,
+    time: f32,
+    tags: &'a [(Box<str>, f32)],
+}
+
+#[derive(Serialize, Default)]
+struct Feedback {
+    up: Vec<i32>,
+    down: Vec<i32>,
+    hide: Vec<i32>,
+}
+
+#[derive(Serialize)]
+struct TaskRequest<'a> {
+    version: u32,
+    top_n: usize,
+    worker: WorkerParams<'a>,
+    tasks: Vec<Params<'a>>,
+    feedback: Feedback,
+}
+
+#[derive(Deserialize)]
+struct Scored {
+    id: i32,
+    score: Option<f32>,
+}
+
+#[derive(Deserialize, PartialEq)]
+#[serde(untagged)]
+enum LegacyTask {
+    WithId(f32, f32, Vec<Box<str>>, i32),
+    Plain(f32, f32, Vec<Box<str>>),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TaskResponse {
+    Scored { recommendations: Vec<Scored> },
+    Legacy(Vec<LegacyTask>),
+}
+
+/// Client of the python service in `recommender/`.
+pub struct HttpRecommender {
+    http_client: Client,
+    host: &'static str,
+    top_n: usize,
+}
+
+impl HttpRecommender {
+    pub fn new(http_client: Client, host: &'static str, top_n: usize) -> HttpRecommender {
+        HttpRecommender {
+            http_client,
+            host,
+            top_n,
+        }
+    }
+}
+
+fn complexity(t: &Task) -> f32 {
+    i16::from(t.complexity) as f32
+}
+
+// old services give no ids, tasks are told apart by their parameters, each one used once
+fn match_legacy<'a>(tasks: &[&'a Task], response: Vec<LegacyTask>) -> Vec<&'a Task> {
+    let mut found: Vec<&'a Task> = vec![];
+    for recommended in response {
+        let task = match recommended {
+            LegacyTask::WithId(.., id) => tasks.iter().find(|t| t.id == id),
+            LegacyTask::Plain(c, time, tags) => tasks.iter().find(|t| {
+                complexity(t) == c
+                    && t.expected_time == time
+                    && t.tags == tags
+                    && !found.iter().any(|f| f.id == t.id)
+            }),
+        };
+        if let Some(t) = task {
+            if !found.iter().any(|f| f.id == t.id) {
+                found.push(t);
+            }
+        }
+    }
+
+    found
+}
+
+#[async_trait]
+impl Recommender for HttpRecommender {
+    async fn recommend(
+        &self,
+        profile: &Profile,
+        tasks: &[Task],
+    ) -> Result<Vec<Recommendation>, Error> {
+        let mut feedback = Feedback::default();
+        for (id, vote) in &profile.votes {
+            match vote {
+                Vote::Up => feedback.up.push(*id),
+                Vote::Down => feedback.down.push(*id),
+                Vote::Hide => feedback.hide.push(*id),
+            }
+        }
+        let tasks: Vec<&Task> = tasks
+            .iter()
+            .filter(|t| !feedback.hide.contains(&t.id))
+            .collect();
+        let body = TaskRequest {
+            version: PROTOCOL_VERSION,
+            top_n: self.top_n,
+            worker: WorkerParams {
+                complexity: profile.complexity,
+                time: profile.time,
+                tags: &profile.tags,
+            },
+            tasks: tasks
+                .iter()
+                .map(|t| Params {
+                    complexity: complexity(t),
+                    time: t.expected_time,
+                    tags: &t.tags,
+                    id: t.id,
+                })
+                .collect(),
+            feedback,
+        };
+        let response: TaskResponse = self
+            .http_client
+            .post(self.host)
+            .json(&body)
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        Ok(match response {
+            TaskResponse::Scored { recommendations } => recommendations
+                .into_iter()
+                .filter_map(|r| {
+                    let t = tasks.iter().find(|t| t.id == r.id)?;
+                    Some(Recommendation::new(profile, t, r.score))
+                })
+                .take(self.top_n)
+                .collect(),
+            TaskResponse::Legacy(response) => {
+                let found = match_legacy(&tasks, response)
+                    .into_iter()
+                    .map(|t| Recommendation::new(profile, t, None))
+                    .collect();
+                rerank(profile, found)
+                    .into_iter()
+                    .take(self.top_n)
+                    .collect()
+            }
+        })
+    }
+}
@@ -0,0 +1,154 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::entities::{recommendation::Vote, task::Task};
+
+mod http;
+mod native;
+mod resilient;
+
+pub use http::HttpRecommender;
+pub use native::NativeRecommender;
+pub use resilient::{ResilientRecommender, Stats};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+
+    #[error("recommender timed out")]
+    Timeout,
+
+    #[error("recommender is paused after repeated failures")]
+    Unavailable,
+}
+
+/// What the recommender knows about the user.
+pub struct Profile {
+    pub user_id: i32,
+    /// Average complexity of completed quests, 0 (C) to 2 (A).
+    pub complexity: f32,
+    /// Average hours spent on a quest.
+    pub time: f32,
+    /// Tags weighted by proficiency.
+    pub tags: Vec<(Box<str>, f32)>,
+    /// What the user said about earlier recommendations, by task id.
+    pub votes: Vec<(i32, Vote)>,
+}
+
+impl Profile {
+    fn vote(&self, task_id: i32) -> Option<Vote> {
+        self.votes
+            .iter()
+            .find(|(id, _)| *id == task_id)
+            .map(|(_, v)| *v)
+    }
+}
+
+/// Why a task was recommended.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Explanation {
+    /// Tags of the task the user has skills in, strongest first.
+    pub tags: Vec<Box<str>>,
+    /// 1 when the task matches the user's level, 0 when it is two classes off.
+    pub complexity_fit: f32,
+    /// 1 when the task takes as long as the user's quests usually do.
+    pub time_fit: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Recommendation {
+    pub task_id: i32,
+    /// How well the task suits the user, 0 to 1.
+    pub score: Option<f32>,
+    pub explanation: Explanation,
+}
+
+impl Recommendation {
+    fn new(profile: &Profile, task: &Task, score: Option<f32>) -> Recommendation {
+        Recommendation {
+            task_id: task.id,
+            score,
+            explanation: explain(profile, task),
+        }
+    }
+}
+
+// score changes for voted tasks, hidden ones are never recommended
+const UPVOTE_BONUS: f32 = 0.15;
+const DOWNVOTE_PENALTY: f32 = 0.3;
+
+fn complexity_fit(profile: &Profile, task: &Task) -> f32 {
+    let c: i16 = task.complexity.into();
+    1.0 - ((c as f32 - profile.complexity).abs() / 2.0).min(1.0)
+}
+
+fn time_fit(profile: &Profile, task: &Task) -> f32 {
+    if profile.time <= 0.0 || task.expected_time <= 0.0 {
+        return 0.0;
+    }
+    profile.time.min(task.expected_time) / profile.time.max(task.expected_time)
+}
+
+fn explain(profile: &Profile, task: &Task) -> Explanation {
+    let mut tags: Vec<(&Box<str>, f32)> = task
+        .tags
+        .iter()
+        .filter_map(|tag| {
+            profile
+                .tags
+                .iter()
+                .find(|(t, _)| t.to_lowercase() == tag.to_lowercase())
+                .map(|(_, w)| (tag, *w))
+        })
+        .collect();
+    tags.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    Explanation {
+        tags: tags.into_iter().map(|(t, _)| t.clone()).collect(),
+        complexity_fit: complexity_fit(profile, task),
+        time_fit: time_fit(profile, task),
+    }
+}
+
+// score of the task after the user's vote, none for hidden tasks
+fn adjust(profile: &Profile, task_id: i32, score: f32) -> Option<f32> {
+    match profile.vote(task_id) {
+        Some(Vote::Hide) => None,
+        Some(Vote::Up) => Some(score + UPVOTE_BONUS),
+        Some(Vote::Down) => Some(score - DOWNVOTE_PENALTY),
+        None => Some(score),
+    }
+}
+
+// moves voted tasks up or down and drops hidden ones, keeping the rest in order
+fn rerank(profile: &Profile, found: Vec<Recommendation>) -> Vec<Recommendation> {
+    let n = found.len().max(1) as f32;
+    let mut ranked: Vec<(f32, Recommendation)> = found
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, r)| {
+            // legacy services give no scores, their rank stands in
+            let base = r.score.unwrap_or(1.0 - i as f32 / n);
+            Some((adjust(profile, r.task_id, base)?, r))
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    ranked.into_iter().map(|(_, r)| r).collect()
+}
+
+#[async_trait]
+pub trait Recommender: Send + Sync {
+    /// Tasks that suit the user best, best first.
+    async fn recommend(
+        &self,
+        profile: &Profile,
+        tasks: &[Task],
+    ) -> Result<Vec<Recommendation>, Error>;
+
+    /// Call counters, only wrappers that track them return some.
+    fn stats(&self) -> Option<Stats> {
+        None
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+
+use super::{adjust, complexity_fit, time_fit, Error, Profile, Recommendation, Recommender};
+use crate::entities::task::Task;
+
+// share of each factor in the final score
+const TAG_WEIGHT: f32 = 0.6;
+const COMPLEXITY_WEIGHT: f32 = 0.25;
+const TIME_WEIGHT: f32 = 0.15;
+// tasks scored lower are not worth highlighting
+const MIN_SCORE: f32 = 0.4;
+
+/// In-process recommender, needs no external service.
+/// Scores tasks by TF-IDF similarity of tags, distance to the user's
+/// complexity level and how well the expected time fits the user's pace,
+/// then moves voted tasks up or down.
+pub struct NativeRecommender {
+    top_n: usize,
+}
+
+impl NativeRecommender {
+    pub fn new(top_n: usize) -> NativeRecommender {
+        NativeRecommender { top_n }
+    }
+}
+
+// rare tags among the candidates say more about a task than common ones
+fn idf(tasks: &[Task]) -> HashMap<String, f32> {
+    let mut df: HashMap<String, f32> = HashMap::new();
+    for t in tasks {
+        for tag in &t.tags {
+            *df.entry(tag.to_lowercase()).or_default() += 1.0;
+        }
+    }
+    let n = tasks.len() as f32;
+
+    df.into_iter()
+        .map(|(tag, df)| (tag, (1.0 + n / df).ln()))
+        .collect()
+}
+
+// cosine between the weighted user tags and the task tags, both scaled by idf
+fn tag_similarity(user: &HashMap<String, f32>, task: &Task, idf: &HashMap<String, f32>) -> f32 {
+    let mut dot = 0.0;
+    let mut task_norm = 0.0;
+    for tag in &task.tags {
+        let tag = tag.to_lowercase();
+        let w = idf.get(&tag).copied().unwrap_or(1.0);
+        task_norm += w * w;
+        if let Some(u) = user.get(&tag) {
+            dot += u * w * w;
+        }
+    }
+    let user_norm: f32 = user
+        .iter()
+        .map(|(tag, u)| {
+            let w = idf.get(tag).copied().unwrap_or(1.0);
+            (u * w).powi(2)
+        })
+        .sum();
+
+    if dot == 0.0 {
+        return 0.0;
+    }
+    dot / (user_norm.sqrt() * task_norm.sqrt())
+}
+
+#[async_trait]
+impl Recommender for NativeRecommender {
+    async fn recommend(
+        &self,
+        profile: &Profile,
+        tasks: &[Task],
+    ) -> Result<Vec<Recommendation>, Error> {
+        let idf = idf(tasks);
+        let mut user: HashMap<String, f32> = HashMap::new();
+        for (tag, weight) in &profile.tags {
+            *user.entry(tag.to_lowercase()).or_default() += weight.max(0.0);
+        }
+
+        let mut scored: Vec<(usize, f32, f32)> = tasks
+            .iter()
+            .enumerate()
+            .filter_map(|(i, t)| {
+                // without known skills only level and pace matter
+                let tags = if user.is_empty() {
+                    1.0
+                } else {
+                    tag_similarity(&user, t, &idf)
+                };
+                let score = TAG_WEIGHT * tags
+                    + COMPLEXITY_WEIGHT * complexity_fit(profile, t)
+                    + TIME_WEIGHT * time_fit(profile, t);
+                Some((i, score, adjust(profile, t.id, score)?))
+            })
+            .filter(|(_, _, adjusted)| *adjusted >= MIN_SCORE)
+            .collect();
+        scored.sort_by(|a, b| b.2.total_cmp(&a.2).then(a.0.cmp(&b.0)));
+
+        Ok(scored
+            .into_iter()
+            .take(self.top_n)
+            .map(|(i, score, _)| Recommendation::new(profile, &tasks[i], Some(score.min(1.0))))
+            .collect())
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use serde::Serialize;
+use tracing::instrument;
+
+use super::{Error, Profile, Recommendation, Recommender};
+use crate::entities::task::Task;
+
+// failures in a row that open the circuit
+const FAILURE_THRESHOLD: u32 = 3;
+// how long an open circuit skips the recommender
+const COOLDOWN: Duration = Duration::from_secs(60);
+const CACHE_TTL: Duration = Duration::from_secs(300);
+const CACHE_SIZE: usize = 1024;
+
+// user id and version of the task set
+type CacheKey = (i32, u64);
+
+#[derive(Default)]
+struct Breaker {
+    failures: u32,
+    open_until: Option<Instant>,
+    // one call is trying the recommender after the cooldown
+    probing: bool,
+}
+
+#[derive(Default)]
+struct Counters {
+    calls: AtomicU64,
+    cache_hits: AtomicU64,
+    failures: AtomicU64,
+    timeouts: AtomicU64,
+    short_circuits: AtomicU64,
+    latency_ms: AtomicU64,
+}
+
+#[derive(Serialize, Clone, Copy)]
+pub struct Stats {
+    pub calls: u64,
+    pub cache_hits: u64,
+    pub failures: u64,
+    pub timeouts: u64,
+    pub short_circuits: u64,
+    /// Total time spent in the inner recommender.
+    pub latency_ms: u64,
+    pub circuit_open: bool,
+}
+
+/// Wraps a recommender with a timeout, a circuit breaker and a cache, so a slow
+/// or dead service costs the quest board at most one timeout per cooldown.
+pub struct ResilientRecommender<R> {
+    inner: R,
+    timeout: Duration,
+    breaker: Mutex<Breaker>,
+    cache: Mutex<HashMap<CacheKey, (Instant, Vec<Recommendation>)>>,
+    counters: Counters,
+}
+
+// changes whenever a task is added, taken or edited, or the user's skills change
+fn version(profile: &Profile, tasks: &[Task]) -> u64 {
+    let mut h = DefaultHasher::new();
+    for t in tasks {
+        t.id.hash(&mut h);
+        i16::from(t.complexity).hash(&mut h);
+        t.expected_time.to_bits().hash(&mut h);
+        t.tags.hash(&mut h);
+    }
+    profile.complexity.to_bits().hash(&mut h);
+    profile.time.to_bits().hash(&mut h);
+    for (tag, weight) in &profile.tags {
+        tag.hash(&mut h);
+        // proficiency decays slowly, small changes should not miss the cache
+        ((weight * 10.0).round() as i64).hash(&mut h);
+    }
+    profile.votes.hash(&mut h);
+    h.finish()
+}
+
+impl<R: Recommender> ResilientRecommender<R> {
+    pub fn new(inner: R, timeout: Duration) -> ResilientRecommender<R> {
+        ResilientRecommender {
+            inner,
+            timeout,
+            breaker: Mutex::default(),
+            cache: Mutex::default(),
+            counters: Counters::default(),
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        let mut breaker = self.breaker.lock().unwrap();
+        match breaker.open_until {
+            Some(until) if until > Instant::now() => true,
+            Some(_) => {
+                // half open: let one call through, a failure opens it again. The others
+                // keep waiting until it answers, or for one more cooldown if it never does
+                breaker.open_until = Some(Instant::now() + COOLDOWN);
+                breaker.failures = FAILURE_THRESHOLD - 1;
+                breaker.probing = true;
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn record(&self, ok: bool) {
+        let mut breaker = self.breaker.lock().unwrap();
+        let probed = std::mem::take(&mut breaker.probing);
+        if ok {
+            if breaker.failures > 0 {
+                tracing::info!(failures = breaker.failures, "recommender recovered");
+            }
+            breaker.failures = 0;
+            if probed {
+                breaker.open_until = None;
+            }
+            return;
+        }
+        breaker.failures += 1;
+        if breaker.failures >= FAILURE_THRESHOLD {
+            tracing::warn!(
+                failures = breaker.failures,
+                cooldown_s = COOLDOWN.as_secs(),
+                "recommender keeps failing, pausing calls"
+            );
+            breaker.open_until = Some(Instant::now() + COOLDOWN);
+        }
+    }
+
+    fn cached(&self, key: &CacheKey) -> Option<Vec<Recommendation>> {
+        let cache = self.cache.lock().unwrap();
+        cache
+            .get(key)
+            .filter(|(at, _)| at.elapsed() < CACHE_TTL)
+            .map(|(_, found)| found.clone())
+    }
+
+    fn store(&self, key: CacheKey, found: Vec<Recommendation>) {
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= CACHE_SIZE {
+            cache.retain(|_, (at, _)| at.elapsed() < CACHE_TTL);
+            if cache.len() >= CACHE_SIZE {
+                cache.clear();
+            }
+        }
+        cache.insert(key, (Instant::now(), found));
+    }
+
+    fn snapshot(&self) -> Stats {
+        let c = &self.counters;
+        Stats {
+            calls: c.calls.load(Ordering::Relaxed),
+            cache_hits: c.cache_hits.load(Ordering::Relaxed),
+            failures: c.failures.load(Ordering::Relaxed),
+            timeouts: c.timeouts.load(Ordering::Relaxed),
+            short_circuits: c.short_circuits.load(Ordering::Relaxed),
+            latency_ms: c.latency_ms.load(Ordering::Relaxed),
+            circuit_open: self
+                .breaker
+                .lock()
+                .unwrap()
+                .open_until
+                .is_some_and(|until| until > Instant::now()),
+        }
+    }
+}
+
+#[async_trait]
+impl<R: Recommender> Recommender for ResilientRecommender<R> {
+    #[instrument(
+        level = "debug",
+        name = "recommend",
+        skip_all,
+        fields(user_id = profile.user_id, tasks = tasks.len())
+    )]
+    async fn recommend(
+        &self,
+        profile: &Profile,
+        tasks: &[Task],
+    ) -> Result<Vec<Recommendation>, Error> {
+        let c = &self.counters;
+        c.calls.fetch_add(1, Ordering::Relaxed);
+
+        let key = (profile.user_id, version(profile, tasks));
+        if let Some(found) = self.cached(&key) {
+            c.cache_hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(found);
+        }
+        if self.is_open() {
+            c.short_circuits.fetch_add(1, Ordering::Relaxed);
+            return Err(Error::Unavailable);
+        }
+
+        let started = Instant::now();
+        let result = tokio::time::timeout(self.timeout, self.inner.recommend(profile, tasks)).await;
+        c.latency_ms
+            .fetch_add(started.elapsed().as_millis() as u64, Ordering::Relaxed);
+
+        match result {
+            Ok(Ok(found)) => {
+                self.record(true);
+                self.store(key, found.clone());
+                Ok(found)
+            }
+            Ok(Err(e)) => {
+                c.failures.fetch_add(1, Ordering::Relaxed);
+                tracing::warn!(error = %e, "recommender failed");
+                self.record(false);
+                Err(e)
+            }
+            Err(_) => {
+                c.timeouts.fetch_add(1, Ordering::Relaxed);
+                tracing::warn!(
+                    timeout_ms = self.timeout.as_millis() as u64,
+                    "recommender did not answer"
+                );
+                self.record(false);
+                Err(Error::Timeout)
+            }
+        }
+    }
+
+    fn stats(&self) -> Option<Stats> {
+        Some(self.snapshot())
+    }
+}
@@ -0,0 +1,64 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+};
+
+use async_trait::async_trait;
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::request::Parts,
+};
+use serde_json::Value;
+
+use super::{db::DbClient, logging::LogErr};
+use crate::entities::{
+    audit::{self, Action, NewEntry, Target},
+    user::User,
+};
+
+/// Where a request came from, taken by the handlers that leave a trace in the audit log.
+/// The address is the peer one, a proxy in front shows up as itself.
+///
+/// Entries are written after the action and outside of its transaction, so an action
+/// whose entry failed to insert stays unaudited, the failure is only in the logs.
+pub struct Audit {
+    ip: Option<IpAddr>,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Audit {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        Ok(Audit {
+            ip: parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|c| c.0.ip()),
+        })
+    }
+}
+
+impl Audit {
+    /// The action has already happened, failing to record it is only logged.
+    pub async fn record(
+        &self,
+        db_client: &DbClient<'_>,
+        actor: Option<&User>,
+        action: Action,
+        target: Target,
+        before: Option<Value>,
+        after: Option<Value>,
+    ) {
+        let entry = NewEntry {
+            actor_id: actor.map(|u| u.id),
+            actor_login: actor.map(|u| &*u.login),
+            action,
+            target,
+            before,
+            after,
+            ip: self.ip,
+        };
+        let _ = audit::create(db_client, &entry).await.log_err();
+    }
+}
@@ -15,7 +15,7 @@ impl AuthUser for User {
     }
 
     fn session_auth_hash(&self) -> &[u8] {
-        &self.pw_hash.as_bytes()
+        self.pw_hash.as_bytes()
     }
 }
 
@@ -36,6 +36,12 @@ pub struct Credentials {
     password: String,
 }
 
+impl Credentials {
+    pub fn login(&self) -> &str {
+        &self.login
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error(transparent)]
@@ -0,0 +1,243 @@
+use std::fmt::Write;
+
+// charts are inline SVG drawn on the server, in the colors and font of rpgui
+pub const GOLD: &str = "#ff0";
+pub const SKY: &str = "#6cf";
+pub const RED: &str = "#f44";
+
+const TEXT: &str = "#fff";
+const GRID: &str = "#776";
+const FONT: &str = "font-family=\"'Press Start 2P', cursive\" font-size=\"8\"";
+
+const WIDTH: f64 = 560.0;
+const HEIGHT: f64 = 240.0;
+const LEFT: f64 = 48.0;
+const RIGHT: f64 = 8.0;
+const TOP: f64 = 24.0;
+const BOTTOM: f64 = 32.0;
+const MAX_LABELS: usize = 8;
+
+pub struct Series<'a> {
+    pub name: &'a str,
+    pub color: &'a str,
+    pub values: Vec<f64>,
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// the closest 1, 2 or 5 times a power of ten above the value, so the grid gets round numbers
+fn nice_max(value: f64) -> f64 {
+    if value <= 0.0 || !value.is_finite() {
+        return 1.0;
+    }
+    let magnitude = 10f64.powf(value.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|m| m * magnitude)
+        .find(|m| *m >= value)
+        .unwrap_or(10.0 * magnitude)
+}
+
+fn format_value(v: f64) -> String {
+    if v.fract() == 0.0 {
+        format!("{v:.0}")
+    } else {
+        format!("{v:.1}")
+    }
+}
+
+fn open(out: &mut String, width: f64, height: f64) {
+    let _ = write!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {width} {height}\" width=\"100%\" shape-rendering=\"crispEdges\" {FONT}>"
+    );
+}
+
+fn max_of(series: &[Series]) -> f64 {
+    nice_max(
+        series
+            .iter()
+            .flat_map(|s| s.values.iter().copied())
+            .fold(0.0, f64::max),
+    )
+}
+
+// grid, value axis, base line and a label under every few groups
+fn axes(out: &mut String, labels: &[String], max: f64) {
+    let plot_w = WIDTH - LEFT - RIGHT;
+    let plot_h = HEIGHT - TOP - BOTTOM;
+    for i in 0..=4 {
+        let v = max * f64::from(i) / 4.0;
+        let y = TOP + plot_h - plot_h * v / max;
+        let _ = write!(
+            out,
+            "<line x1=\"{LEFT}\" y1=\"{y}\" x2=\"{}\" y2=\"{y}\" stroke=\"{GRID}\" stroke-dasharray=\"2 2\"/>\
+            <text x=\"{}\" y=\"{}\" fill=\"{TEXT}\" text-anchor=\"end\">{}</text>",
+            WIDTH - RIGHT,
+            LEFT - 4.0,
+            y + 3.0,
+            format_value(v)
+        );
+    }
+    let group_w = plot_w / labels.len().max(1) as f64;
+    let label_step = labels.len().div_ceil(MAX_LABELS).max(1);
+    for (i, label) in labels.iter().enumerate().step_by(label_step) {
+        let _ = write!(
+            out,
+            "<text x=\"{:.1}\" y=\"{}\" fill=\"{TEXT}\" text-anchor=\"middle\">{}</text>",
+            LEFT + group_w * (i as f64 + 0.5),
+            HEIGHT - BOTTOM + 14.0,
+            escape(label)
+        );
+    }
+    let _ = write!(
+        out,
+        "<line x1=\"{LEFT}\" y1=\"{0}\" x2=\"{1}\" y2=\"{0}\" stroke=\"{TEXT}\"/>",
+        TOP + plot_h,
+        WIDTH - RIGHT
+    );
+}
+
+fn legend(out: &mut String, series: &[Series]) {
+    let mut x = LEFT;
+    for s in series {
+        let _ = write!(
+            out,
+            "<rect x=\"{x}\" y=\"6\" width=\"8\" height=\"8\" fill=\"{}\"/>\
+            <text x=\"{}\" y=\"14\" fill=\"{TEXT}\">{}</text>",
+            s.color,
+            x + 12.0,
+            escape(s.name)
+        );
+        x += 24.0 + 8.0 * s.name.chars().count() as f64;
+    }
+}
+
+/// Vertical bars for every label, series side by side, with a legend on top.
+pub fn columns(labels: &[String], series: &[Series]) -> String {
+    let mut out = String::new();
+    open(&mut out, WIDTH, HEIGHT);
+
+    let plot_w = WIDTH - LEFT - RIGHT;
+    let plot_h = HEIGHT - TOP - BOTTOM;
+    let max = max_of(series);
+    axes(&mut out, labels, max);
+
+    let group_w = plot_w / labels.len().max(1) as f64;
+    let bar_w = (group_w * 0.8 / series.len().max(1) as f64).max(1.0);
+    for (i, label) in labels.iter().enumerate() {
+        let x0 = LEFT + group_w * i as f64 + group_w * 0.1;
+        for (j, s) in series.iter().enumerate() {
+            let v = s.values.get(i).copied().unwrap_or_default();
+            let h = plot_h * v / max;
+            let _ = write!(
+                out,
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{bar_w:.1}\" height=\"{h:.1}\" fill=\"{}\"><title>{}: {}</title></rect>",
+                x0 + bar_w * j as f64,
+                TOP + plot_h - h,
+                s.color,
+                escape(label),
+                format_value(v)
+            );
+        }
+    }
+    legend(&mut out, series);
+
+    out.push_str("</svg>");
+    out
+}
+
+/// A line per series through the middle of every label, a series shorter than
+/// the labels stops early, as the actual line of a burndown does at today.
+pub fn lines(labels: &[String], series: &[Series]) -> String {
+    let mut out = String::new();
+    open(&mut out, WIDTH, HEIGHT);
+
+    let plot_w = WIDTH - LEFT - RIGHT;
+    let plot_h = HEIGHT - TOP - BOTTOM;
+    let max = max_of(series);
+    axes(&mut out, labels, max);
+
+    let group_w = plot_w / labels.len().max(1) as f64;
+    for s in series {
+        let points: Vec<_> = s
+            .values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                (
+                    LEFT + group_w * (i as f64 + 0.5),
+                    TOP + plot_h - plot_h * v / max,
+                )
+            })
+            .collect();
+        let _ = write!(
+            out,
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"/>",
+            points
+                .iter()
+                .map(|(x, y)| format!("{x:.1},{y:.1}"))
+                .collect::<Vec<_>>()
+                .join(" "),
+            s.color
+        );
+        for ((x, y), (label, v)) in points.iter().zip(labels.iter().zip(&s.values)) {
+            let _ = write!(
+                out,
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"4\" height=\"4\" fill=\"{}\"><title>{}: {}</title></rect>",
+                x - 2.0,
+                y - 2.0,
+                s.color,
+                escape(label),
+                format_value(*v)
+            );
+        }
+    }
+    legend(&mut out, series);
+
+    out.push_str("</svg>");
+    out
+}
+
+/// Horizontal bars, one per row, for long labels such as logins and tags.
+pub fn bars(rows: &[(String, f64)], color: &str) -> String {
+    const ROW: f64 = 16.0;
+    const LABEL_W: f64 = 160.0;
+    const VALUE_W: f64 = 48.0;
+
+    let height = ROW * rows.len().max(1) as f64 + 8.0;
+    let mut out = String::new();
+    open(&mut out, WIDTH, height);
+
+    let max = nice_max(rows.iter().map(|(_, v)| *v).fold(0.0, f64::max));
+    let plot_w = WIDTH - LABEL_W - VALUE_W;
+    for (i, (label, v)) in rows.iter().enumerate() {
+        let y = 4.0 + ROW * i as f64;
+        // a label longer than its column is cut, the full one is in the tooltip
+        let short: String = label.chars().take(18).collect();
+        let w = plot_w * v / max;
+        let _ = write!(
+            out,
+            "<text x=\"{}\" y=\"{}\" fill=\"{TEXT}\" text-anchor=\"end\"><title>{}</title>{}</text>\
+            <rect x=\"{LABEL_W}\" y=\"{}\" width=\"{w:.1}\" height=\"{}\" fill=\"{color}\"/>\
+            <text x=\"{:.1}\" y=\"{}\" fill=\"{TEXT}\">{}</text>",
+            LABEL_W - 6.0,
+            y + 10.0,
+            escape(label),
+            escape(&short),
+            y + 2.0,
+            ROW - 4.0,
+            LABEL_W + w + 4.0,
+            y + 10.0,
+            format_value(*v)
+        );
+    }
+
+    out.push_str("</svg>");
+    out
+}
@@ -0,0 +1,491 @@
+use std::{
+    env, fmt, fs, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use serde::Deserialize;
+use tokio_postgres::config::SslMode;
+use tracing_subscriber::EnvFilter;
+
+use super::jobs::Schedule;
+
+const DEFAULT_PATH: &str = "config.toml";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to read {path}: {source}")]
+    Read { path: PathBuf, source: io::Error },
+
+    #[error("failed to parse {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[error("${var} has an invalid value {value:?}")]
+    Env { var: &'static str, value: String },
+
+    #[error("{field}: {reason}")]
+    Invalid { field: &'static str, reason: String },
+}
+
+/// Settings of the whole server. Read from the TOML file at `$CONFIG_PATH`
+/// (`config.toml` by default, may be absent), then overridden by env variables,
+/// see `config.example.toml`.
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub listen: SocketAddr,
+    /// Directory with `templates` and `dist`.
+    pub static_path: PathBuf,
+    pub db: Db,
+    pub storage: Storage,
+    pub recommender: Recommender,
+    pub sessions: Sessions,
+    pub features: Features,
+    pub rules: Rules,
+    pub logging: Logging,
+    pub jobs: Jobs,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Db {
+    /// Full connection url, replaces host, port, user and, when it has them, password and name.
+    /// Its sslmode is only checked against `tls`, which decides.
+    pub url: Option<String>,
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub name: String,
+    pub password_path: PathBuf,
+    /// Schema applied on every start, must be idempotent.
+    pub init_path: PathBuf,
+    pub pool_size: u32,
+    pub tls: TlsMode,
+    /// PEM bundle of the certificate authorities the server certificate is checked against,
+    /// the system ones when unset.
+    pub ca_path: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TlsMode {
+    Disable,
+    /// Encrypted, the certificate is checked only against `ca_path` when it is set.
+    Require,
+    /// Encrypted, the certificate must be trusted and issued for the host.
+    VerifyFull,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Storage {
+    pub path: PathBuf,
+    /// Upload limit in bytes.
+    pub max_attachment_size: usize,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RecommenderKind {
+    /// The python service at `host`.
+    Http,
+    /// The in-process one.
+    Native,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Recommender {
+    pub kind: RecommenderKind,
+    pub host: String,
+    /// How many quests get highlighted on the board.
+    pub top_n: usize,
+    pub timeout_ms: u64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sessions {
+    pub cookie_name: String,
+    /// Send the cookie over https only.
+    pub secure: bool,
+    /// Sessions left alone this long end, they last until the browser closes otherwise.
+    pub inactivity_hours: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(default, deny_unknown_fields)]
+pub struct Features {
+    pub attachments: bool,
+    pub comments: bool,
+    pub recommendations: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(default, deny_unknown_fields)]
+pub struct Rules {
+    /// Xp multiplier for quests finished a day or more before the deadline.
+    pub early_bonus: f32,
+    /// Xp multiplier for quests finished after the deadline.
+    pub late_penalty: f32,
+    /// Hours an overdue quest stays assigned before it returns to the board, forever when unset.
+    pub overdue_grace_hours: Option<f64>,
+    /// The class of an adventurer is reconsidered every this many quests.
+    pub calibration_interval: i64,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable lines.
+    Text,
+    /// One JSON object per line, for log collectors.
+    Json,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Logging {
+    /// Directives in the `RUST_LOG` syntax, e.g. `info,server=debug`.
+    pub filter: String,
+    pub format: LogFormat,
+}
+
+/// Schedules of the background jobs, see `Schedule`.
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Jobs {
+    pub overdue_check: Schedule,
+    pub session_cleanup: Schedule,
+    pub leaderboard_snapshot: Schedule,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            listen: SocketAddr::from(([0, 0, 0, 0], 3000)),
+            static_path: "static".into(),
+            db: Db::default(),
+            storage: Storage::default(),
+            recommender: Recommender::default(),
+            sessions: Sessions::default(),
+            features: Features::default(),
+            rules: Rules::default(),
+            logging: Logging::default(),
+            jobs: Jobs::default(),
+        }
+    }
+}
+
+impl Default for Db {
+    fn default() -> Self {
+        Db {
+            url: None,
+            host: "localhost".to_owned(),
+            port: 5432,
+            user: "dungeon".to_owned(),
+            name: "dungeon".to_owned(),
+            password_path: "/run/secrets/db_password".into(),
+            init_path: "init.sql".into(),
+            pool_size: 10,
+            tls: TlsMode::Disable,
+            ca_path: None,
+        }
+    }
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Storage {
+            path: "uploads".into(),
+            max_attachment_size: 10 * 1024 * 1024,
+        }
+    }
+}
+
+impl Default for Recommender {
+    fn default() -> Self {
+        Recommender {
+            kind: RecommenderKind::Http,
+            host: "http://localhost:8080".to_owned(),
+            top_n: 5,
+            timeout_ms: 2000,
+        }
+    }
+}
+
+impl Default for Sessions {
+    fn default() -> Self {
+        Sessions {
+            cookie_name: "id".to_owned(),
+            secure: false,
+            inactivity_hours: None,
+        }
+    }
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Features {
+            attachments: true,
+            comments: true,
+            recommendations: true,
+        }
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            early_bonus: 1.2,
+            late_penalty: 0.5,
+            overdue_grace_hours: None,
+            calibration_interval: 10,
+        }
+    }
+}
+
+impl Default for Logging {
+    fn default() -> Self {
+        Logging {
+            filter: "info".to_owned(),
+            format: LogFormat::Text,
+        }
+    }
+}
+
+impl Default for Jobs {
+    fn default() -> Self {
+        Jobs {
+            overdue_check: Schedule::Every(Duration::from_secs(5 * 60)),
+            session_cleanup: Schedule::Every(Duration::from_secs(60 * 60)),
+            leaderboard_snapshot: "0 0 * * *".parse().unwrap(),
+        }
+    }
+}
+
+impl FromStr for RecommenderKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "http" => Ok(RecommenderKind::Http),
+            "native" => Ok(RecommenderKind::Native),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for TlsMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "disable" => Ok(TlsMode::Disable),
+            "require" => Ok(TlsMode::Require),
+            "verify-full" => Ok(TlsMode::VerifyFull),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+// replaces the value with the parsed env variable when it is set
+fn env_override<T: FromStr>(value: &mut T, var: &'static str) -> Result<(), Error> {
+    if let Ok(v) = env::var(var) {
+        *value = v.parse().map_err(|_| Error::Env { var, value: v })?;
+    }
+    Ok(())
+}
+
+fn env_override_opt<T: FromStr>(value: &mut Option<T>, var: &'static str) -> Result<(), Error> {
+    if let Ok(v) = env::var(var) {
+        *value = Some(v.parse().map_err(|_| Error::Env { var, value: v })?);
+    }
+    Ok(())
+}
+
+fn invalid(field: &'static str, reason: impl fmt::Display) -> Error {
+    Error::Invalid {
+        field,
+        reason: reason.to_string(),
+    }
+}
+
+fn readable(field: &'static str, path: &Path) -> Result<(), Error> {
+    fs::metadata(path)
+        .map(|_| ())
+        .map_err(|e| invalid(field, format!("{}: {e}", path.display())))
+}
+
+impl Config {
+    /// Reads, overrides and validates the config, the error tells what to fix.
+    pub fn load() -> Result<Config, Error> {
+        let path = env::var("CONFIG_PATH").ok();
+        let mut config = match fs::read_to_string(path.as_deref().unwrap_or(DEFAULT_PATH)) {
+            Ok(s) => toml::from_str(&s).map_err(|source| Error::Parse {
+                path: path.as_deref().unwrap_or(DEFAULT_PATH).into(),
+                source,
+            })?,
+            // the file is optional unless asked for explicitly
+            Err(e) if e.kind() == io::ErrorKind::NotFound && path.is_none() => Config::default(),
+            Err(source) => {
+                return Err(Error::Read {
+                    path: path.unwrap_or(DEFAULT_PATH.to_owned()).into(),
+                    source,
+                })
+            }
+        };
+        config.override_from_env()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn override_from_env(&mut self) -> Result<(), Error> {
+        env_override(&mut self.listen, "LISTEN_ADDR")?;
+        env_override(&mut self.static_path, "STATIC_PATH")?;
+
+        let db = &mut self.db;
+        env_override_opt(&mut db.url, "DATABASE_URL")?;
+        env_override(&mut db.host, "DB_HOST")?;
+        env_override(&mut db.port, "DB_PORT")?;
+        env_override(&mut db.user, "DB_USER")?;
+        env_override(&mut db.name, "DB_NAME")?;
+        env_override(&mut db.password_path, "DB_PASSWORD_PATH")?;
+        env_override(&mut db.init_path, "DB_INIT_PATH")?;
+        env_override(&mut db.pool_size, "DB_POOL_SIZE")?;
+        env_override(&mut db.tls, "DB_TLS")?;
+        env_override_opt(&mut db.ca_path, "DB_CA_PATH")?;
+
+        env_override(&mut self.storage.path, "STORAGE_PATH")?;
+        env_override(&mut self.storage.max_attachment_size, "MAX_ATTACHMENT_SIZE")?;
+
+        let recommender = &mut self.recommender;
+        env_override(&mut recommender.kind, "RECOMMENDER")?;
+        env_override(&mut recommender.host, "AI_HOST")?;
+        env_override(&mut recommender.top_n, "RECOMMENDATIONS")?;
+        env_override(&mut recommender.timeout_ms, "RECOMMENDER_TIMEOUT_MS")?;
+
+        env_override(&mut self.sessions.secure, "SESSION_SECURE")?;
+        env_override_opt(
+            &mut self.sessions.inactivity_hours,
+            "SESSION_INACTIVITY_HOURS",
+        )?;
+
+        env_override_opt(&mut self.rules.overdue_grace_hours, "OVERDUE_GRACE_HOURS")?;
+
+        env_override(&mut self.logging.filter, "RUST_LOG")?;
+        env_override(&mut self.logging.format, "LOG_FORMAT")?;
+
+        env_override(&mut self.jobs.overdue_check, "JOBS_OVERDUE_CHECK")?;
+        env_override(&mut self.jobs.session_cleanup, "JOBS_SESSION_CLEANUP")?;
+        env_override(
+            &mut self.jobs.leaderboard_snapshot,
+            "JOBS_LEADERBOARD_SNAPSHOT",
+        )?;
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        readable("static_path", &self.static_path.join("templates"))?;
+        readable("db.init_path", &self.db.init_path)?;
+        match &self.db.url {
+            Some(url) => {
+                let pg_config: tokio_postgres::Config = url
+                    .parse()
+                    .map_err(|e| invalid("db.url", format!("{e}, sslmode is set with db.tls")))?;
+                if pg_config.get_password().is_none() {
+                    readable("db.password_path", &self.db.password_path)?;
+                }
+                if pg_config.get_ssl_mode() == SslMode::Require && self.db.tls == TlsMode::Disable {
+                    return Err(invalid(
+                        "db.tls",
+                        "the url requires TLS, set it to require or verify-full",
+                    ));
+                }
+            }
+            None => {
+                readable("db.password_path", &self.db.password_path)?;
+                if self.db.host.is_empty() {
+                    return Err(invalid("db.host", "must not be empty"));
+                }
+                if self.db.user.is_empty() {
+                    return Err(invalid("db.user", "must not be empty"));
+                }
+            }
+        }
+        if let Some(path) = &self.db.ca_path {
+            if self.db.tls == TlsMode::Disable {
+                return Err(invalid(
+                    "db.ca_path",
+                    "has no effect while db.tls is disable",
+                ));
+            }
+            readable("db.ca_path", path)?;
+        }
+        if self.db.name.is_empty() {
+            return Err(invalid("db.name", "must not be empty"));
+        }
+        if self.db.pool_size == 0 {
+            return Err(invalid("db.pool_size", "must be at least 1"));
+        }
+        if self.storage.max_attachment_size == 0 {
+            return Err(invalid("storage.max_attachment_size", "must be positive"));
+        }
+        if !(1..=50).contains(&self.recommender.top_n) {
+            return Err(invalid("recommender.top_n", "must be between 1 and 50"));
+        }
+        if self.recommender.timeout_ms == 0 {
+            return Err(invalid("recommender.timeout_ms", "must be positive"));
+        }
+        if self.recommender.kind == RecommenderKind::Http
+            && !self.recommender.host.starts_with("http")
+        {
+            return Err(invalid(
+                "recommender.host",
+                format!("{:?} is not an http url", self.recommender.host),
+            ));
+        }
+        if self.sessions.cookie_name.is_empty() {
+            return Err(invalid("sessions.cookie_name", "must not be empty"));
+        }
+        if self.sessions.inactivity_hours == Some(0) {
+            return Err(invalid("sessions.inactivity_hours", "must be positive"));
+        }
+        if self.rules.early_bonus < 1.0 {
+            return Err(invalid("rules.early_bonus", "must be at least 1"));
+        }
+        if !(0.0..=1.0).contains(&self.rules.late_penalty) {
+            return Err(invalid("rules.late_penalty", "must be between 0 and 1"));
+        }
+        if self.rules.overdue_grace_hours.is_some_and(|h| h < 0.0) {
+            return Err(invalid("rules.overdue_grace_hours", "must not be negative"));
+        }
+        if self.rules.calibration_interval < 1 {
+            return Err(invalid("rules.calibration_interval", "must be at least 1"));
+        }
+        if let Err(e) = EnvFilter::try_new(&self.logging.filter) {
+            return Err(invalid("logging.filter", e));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,131 @@
+/// Appends one record, fields are quoted only when they have to be (RFC 4180).
+pub fn write_record<'a>(out: &mut String, fields: impl IntoIterator<Item = &'a str>) {
+    for (i, field) in fields.into_iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        // spreadsheets run cells starting with these as formulas
+        let field = if field.starts_with(['=', '+', '-', '@']) {
+            format!("'{field}")
+        } else {
+            field.to_owned()
+        };
+        if field.contains([',', '"', '\n', '\r']) {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(&field);
+        }
+    }
+    out.push_str("\r\n");
+}
+
+/// Splits the text into records and fields, the counterpart of `write_record`.
+/// Quoted fields may hold commas, quotes and line breaks; blank lines are skipped.
+pub fn read_records(input: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = input.trim_start_matches('\u{feff}').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => record.push(unescape(std::mem::take(&mut field))),
+            (false, '\r') if chars.peek() == Some(&'\n') => {}
+            (false, '\n' | '\r') => {
+                record.push(unescape(std::mem::take(&mut field)));
+                if record.iter().any(|f| !f.is_empty()) {
+                    records.push(std::mem::take(&mut record));
+                } else {
+                    record.clear();
+                }
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    record.push(unescape(field));
+    if record.iter().any(|f| !f.is_empty()) {
+        records.push(record);
+    }
+    records
+}
+
+// drops the quote `write_record` puts before formula-like fields
+fn unescape(field: String) -> String {
+    match field.strip_prefix('\'') {
+        Some(rest) if rest.starts_with(['=', '+', '-', '@']) => rest.to_owned(),
+        _ => field,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter()
+            .map(|r| r.iter().map(|f| f.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn plain_fields() {
+        assert_eq!(
+            read_records("a,b,c\n1,,3"),
+            records(&[&["a", "b", "c"], &["1", "", "3"]])
+        );
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(
+            read_records("\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\"\r\n"),
+            records(&[&["a,b", "say \"hi\"", "two\nlines"]])
+        );
+        // a quote inside an unquoted field is kept as it is
+        assert_eq!(read_records("5\"2,x"), records(&[&["5\"2", "x"]]));
+    }
+
+    #[test]
+    fn line_endings_and_bom() {
+        let expected = records(&[&["a", "b"], &["1", "2"]]);
+        assert_eq!(read_records("a,b\r\n1,2\r\n"), expected);
+        assert_eq!(read_records("a,b\r1,2\r"), expected);
+        assert_eq!(read_records("\u{feff}a,b\n1,2"), expected);
+    }
+
+    #[test]
+    fn blank_rows_are_skipped() {
+        assert_eq!(
+            read_records("\na,b\n\n,\r\n1,2\n\n"),
+            records(&[&["a", "b"], &["1", "2"]])
+        );
+        assert!(read_records("").is_empty());
+        assert!(read_records("\r\n\r\n").is_empty());
+    }
+
+    #[test]
+    fn formulas_round_trip() {
+        let fields = [
+            "=SUM(A1:A2)",
+            "+1",
+            "-2",
+            "@me",
+            "'quoted",
+            "plain, \"text\"",
+        ];
+        let mut out = String::new();
+        write_record(&mut out, fields);
+        assert!(out.starts_with("'=SUM(A1:A2),'+1,'-2,'@me,'quoted,"));
+        assert_eq!(read_records(&out), records(&[&fields]));
+    }
+}
@@ -1,44 +1,186 @@
-use std::{env, fs};
+use std::{
+    error::Error,
+    fs,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
 
-use bb8::{Pool, PooledConnection, RunError};
+use bb8::{ManageConnection, Pool, PooledConnection, RunError, State};
 use bb8_postgres::{
-    tokio_postgres::{self, NoTls},
+    tokio_postgres::{
+        self,
+        config::SslMode,
+        tls::{MakeTlsConnect, TlsConnect},
+        Client, NoTls, Socket,
+    },
     PostgresConnectionManager,
 };
+use native_tls::{Certificate, TlsConnector};
+use postgres_native_tls::MakeTlsConnector;
+
+use super::config::{Db, TlsMode};
+
+type PlainManager = PostgresConnectionManager<NoTls>;
+type TlsManager = PostgresConnectionManager<MakeTlsConnector>;
 
+/// Connection pool, plain or encrypted depending on `db.tls`.
 #[derive(Clone)]
-pub struct PoolWrapper {
-    inner: &'static Pool<PostgresConnectionManager<NoTls>>,
+pub enum PoolWrapper {
+    Plain(&'static Pool<PlainManager>),
+    Tls(&'static Pool<TlsManager>),
+}
+
+enum Connection {
+    Plain(PooledConnection<'static, PlainManager>),
+    Tls(PooledConnection<'static, TlsManager>),
 }
 
-pub type DbClient<'a> = PooledConnection<'static, PostgresConnectionManager<NoTls>>;
+/// Pooled connection, derefs to the client whatever the transport.
+pub struct DbClient<'a> {
+    conn: Connection,
+    _pool: PhantomData<&'a PoolWrapper>,
+}
+
+impl Deref for DbClient<'_> {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        match &self.conn {
+            Connection::Plain(c) => c,
+            Connection::Tls(c) => c,
+        }
+    }
+}
+
+impl DerefMut for DbClient<'_> {
+    fn deref_mut(&mut self) -> &mut Client {
+        match &mut self.conn {
+            Connection::Plain(c) => c,
+            Connection::Tls(c) => c,
+        }
+    }
+}
 
 impl PoolWrapper {
-    pub async fn try_get(&self) -> Result<DbClient, RunError<tokio_postgres::Error>> {
-        self.inner.get_owned().await
-    }
-}
-
-pub async fn init_db() -> &'static PoolWrapper {
-    let db_user = &env::var("DB_USER").expect("$DB_USER is not provided");
-    let db_host = &env::var("DB_HOST").expect("$DB_HOST is not provided");
-    let db_port = &env::var("DB_PORT").expect("$DB_PORT is not provided");
-    let db_password = fs::read_to_string(
-        &env::var("DB_PASSWORD_PATH").unwrap_or("/run/secrets/db_password".to_string()),
-    )
-    .expect("db_password is not found");
-    let init_sql =
-        fs::read_to_string(&env::var("DB_INIT_PATH").expect("$DB_INIT_PATH is not provided"))
-            .unwrap();
-    let manager = PostgresConnectionManager::new_from_stringlike(
-        &format!("host={db_host} port={db_port} user={db_user} password={db_password}"),
-        NoTls,
-    )
-    .expect("failed to create db connection pool");
-    let pool = Box::leak(Box::new(Pool::builder().build(manager).await.unwrap()));
+    pub async fn try_get(&self) -> Result<DbClient<'_>, RunError<tokio_postgres::Error>> {
+        let conn = match self {
+            PoolWrapper::Plain(pool) => Connection::Plain(pool.get_owned().await?),
+            PoolWrapper::Tls(pool) => Connection::Tls(pool.get_owned().await?),
+        };
+
+        Ok(DbClient {
+            conn,
+            _pool: PhantomData,
+        })
+    }
+
+    pub fn state(&self) -> State {
+        match self {
+            PoolWrapper::Plain(pool) => pool.state(),
+            PoolWrapper::Tls(pool) => pool.state(),
+        }
+    }
+}
+
+// DATABASE_URL when given, the separate settings otherwise;
+// the password file and the database name fill in what the url leaves out
+fn pg_config(config: &Db) -> tokio_postgres::Config {
+    let mut pg_config = match &config.url {
+        Some(url) => url.parse().expect("invalid database url"),
+        None => {
+            let mut c = tokio_postgres::Config::new();
+            c.host(&config.host).port(config.port).user(&config.user);
+            c
+        }
+    };
+    if pg_config.get_password().is_none() {
+        let db_password =
+            fs::read_to_string(&config.password_path).expect("db_password is not found");
+        pg_config.password(db_password.trim_end());
+    }
+    if pg_config.get_dbname().is_none() {
+        pg_config.dbname(&config.name);
+    }
+    pg_config.ssl_mode(match config.tls {
+        TlsMode::Disable => SslMode::Disable,
+        TlsMode::Require | TlsMode::VerifyFull => SslMode::Require,
+    });
+
+    pg_config
+}
+
+// require only encrypts, unless a CA bundle is given, then the chain is checked too;
+// verify-full also checks that the certificate belongs to the host
+fn tls_connector(config: &Db) -> MakeTlsConnector {
+    let mut builder = TlsConnector::builder();
+    if let Some(path) = &config.ca_path {
+        let pem = fs::read(path).expect("CA bundle is not found");
+        for cert in pem_certificates(&pem) {
+            builder.add_root_certificate(cert);
+        }
+    }
+    if config.tls == TlsMode::Require {
+        builder
+            .danger_accept_invalid_certs(config.ca_path.is_none())
+            .danger_accept_invalid_hostnames(true);
+    }
+
+    MakeTlsConnector::new(builder.build().expect("failed to set up TLS"))
+}
+
+// a bundle holds several certificates, native-tls reads them one by one
+fn pem_certificates(pem: &[u8]) -> Vec<Certificate> {
+    const END: &str = "-----END CERTIFICATE-----";
+    let pem = String::from_utf8_lossy(pem);
+
+    pem.split_inclusive(END)
+        .filter(|block| block.contains(END))
+        .map(|block| {
+            Certificate::from_pem(block.trim().as_bytes()).expect("invalid CA certificate")
+        })
+        .collect()
+}
+
+async fn build_pool<T>(
+    config: &Db,
+    pg_config: tokio_postgres::Config,
+    tls: T,
+) -> &'static Pool<PostgresConnectionManager<T>>
+where
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+    T::Stream: Send + Sync,
+    T::TlsConnect: Send,
+    <T::TlsConnect as TlsConnect<Socket>>::Future: Send,
+{
+    let manager = PostgresConnectionManager::new(pg_config, tls);
+    // the pool only reports a timeout, a direct attempt tells why it failed
+    if let Err(e) = manager.connect().await {
+        let cause = e.source().map(|c| format!(": {c}")).unwrap_or_default();
+        panic!("failed to connect to db: {e}{cause}");
+    }
+
+    Box::leak(Box::new(
+        Pool::builder()
+            .max_size(config.pool_size)
+            .build(manager)
+            .await
+            .unwrap(),
+    ))
+}
+
+pub async fn init_db(config: &Db) -> &'static PoolWrapper {
+    let init_sql = fs::read_to_string(&config.init_path).unwrap();
+    let pg_config = pg_config(config);
+    let pool_wrapper = match config.tls {
+        TlsMode::Disable => PoolWrapper::Plain(build_pool(config, pg_config, NoTls).await),
+        TlsMode::Require | TlsMode::VerifyFull => {
+            PoolWrapper::Tls(build_pool(config, pg_config, tls_connector(config)).await)
+        }
+    };
+    let pool_wrapper: &'static PoolWrapper = Box::leak(Box::new(pool_wrapper));
     // initialize db
-    let pool_wrapper = Box::leak(Box::new(PoolWrapper { inner: pool }));
-    pool.get_owned()
+    pool_wrapper
+        .try_get()
         .await
         .unwrap()
         .batch_execute(&init_sql)
@@ -0,0 +1,566 @@
+use std::{
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Days, NaiveDate, TimeDelta, Timelike, Utc};
+use futures::FutureExt;
+use serde::{de, Deserialize, Deserializer};
+use tokio::{
+    sync::watch,
+    task::{JoinHandle, JoinSet},
+};
+use tracing::Instrument;
+
+use super::{
+    config::Rules,
+    db::{DbClient, PoolWrapper},
+    logging::LogErr,
+};
+use crate::entities::{
+    job,
+    leaderboard::{self, Metric},
+    session, task,
+};
+
+// first key of the advisory locks, the second one is the hash of the job name
+const LOCK_SPACE: i32 = 0x6a6f62;
+
+/// When a job runs: `@every 5m` (s, m, h and d units) or a five field cron expression in UTC.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schedule {
+    Every(Duration),
+    Cron(Cron),
+}
+
+/// Minute, hour, day of month, month and day of week, each a `*`, a number, a range
+/// or a comma separated list of them, optionally with a `/step`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cron {
+    expr: Box<str>,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // as in crontab, when both days are restricted either one matches
+    any_day: bool,
+    any_weekday: bool,
+}
+
+fn parse_number(s: &str) -> Result<u32, String> {
+    s.parse().map_err(|_| format!("{s:?} is not a number"))
+}
+
+// one bit per allowed value
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match parse_number(step)? {
+                0 => return Err(format!("zero step in {part:?}")),
+                step => (range, step),
+            },
+            None => (part, 1),
+        };
+        let (from, to) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((from, to)) => (parse_number(from)?, parse_number(to)?),
+            // `5/15` runs from 5 to the end
+            None if step > 1 => (parse_number(range)?, max),
+            None => (parse_number(range)?, parse_number(range)?),
+        };
+        if from < min || to > max || from > to {
+            return Err(format!("{part:?} is out of {min}-{max}"));
+        }
+        for v in (from..=to).step_by(step as usize) {
+            bits |= 1 << v;
+        }
+    }
+
+    Ok(bits)
+}
+
+impl FromStr for Cron {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!("{s:?} must have five fields"));
+        };
+        let (any_day, any_weekday) = (days == "*", weekdays == "*");
+        let mut weekdays = parse_field(weekdays, 0, 7)?;
+        // both 0 and 7 are sunday
+        if weekdays & 1 << 7 != 0 {
+            weekdays = weekdays & !(1 << 7) | 1;
+        }
+        let cron = Cron {
+            expr: fields.join(" ").into(),
+            minutes: parse_field(minutes, 0, 59)?,
+            hours: parse_field(hours, 0, 23)?,
+            days: parse_field(days, 1, 31)?,
+            months: parse_field(months, 1, 12)?,
+            weekdays,
+            any_day,
+            any_weekday,
+        };
+        if cron.next_after(Utc::now()).is_none() {
+            return Err(format!("{s:?} never comes"));
+        }
+
+        Ok(cron)
+    }
+}
+
+impl Cron {
+    fn day_matches(&self, t: DateTime<Utc>) -> bool {
+        let day = self.days & 1 << t.day() != 0;
+        let weekday = self.weekdays & 1 << t.weekday().num_days_from_sunday() != 0;
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+
+    /// The first matching minute after `t`, none if there is no such date within four years.
+    fn next_after(&self, t: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let midnight = |d: NaiveDate| d.and_hms_opt(0, 0, 0).unwrap().and_utc();
+        let mut t = t.with_second(0)?.with_nanosecond(0)? + TimeDelta::minutes(1);
+        let limit = t + TimeDelta::days(4 * 366);
+        while t < limit {
+            if self.months & 1 << t.month() == 0 {
+                let (year, month) = match t.month() {
+                    12 => (t.year() + 1, 1),
+                    m => (t.year(), m + 1),
+                };
+                t = midnight(NaiveDate::from_ymd_opt(year, month, 1)?);
+            } else if !self.day_matches(t) {
+                t = midnight(t.date_naive().checked_add_days(Days::new(1))?);
+            } else if self.hours & 1 << t.hour() == 0 {
+                t += TimeDelta::minutes(60 - t.minute() as i64);
+            } else if self.minutes & 1 << t.minute() == 0 {
+                t += TimeDelta::minutes(1);
+            } else {
+                return Some(t);
+            }
+        }
+
+        None
+    }
+}
+
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("{s:?} is not a duration like 30s, 5m, 2h or 1d");
+    let unit = match s.chars().last() {
+        Some('s') => 1,
+        Some('m') => 60,
+        Some('h') => 60 * 60,
+        Some('d') => 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    match s[..s.len() - 1].parse::<u64>() {
+        Ok(n) if n > 0 => Ok(Duration::from_secs(n * unit)),
+        _ => Err(invalid()),
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().strip_prefix("@every ") {
+            Some(d) => Ok(Schedule::Every(parse_duration(d.trim())?)),
+            None => Ok(Schedule::Cron(s.parse()?)),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schedule::Every(d) => match d.as_secs() {
+                s if s % (24 * 60 * 60) == 0 => write!(f, "@every {}d", s / (24 * 60 * 60)),
+                s if s % (60 * 60) == 0 => write!(f, "@every {}h", s / (60 * 60)),
+                s if s % 60 == 0 => write!(f, "@every {}m", s / 60),
+                s => write!(f, "@every {s}s"),
+            },
+            Schedule::Cron(c) => f.write_str(&c.expr),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Schedule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl Schedule {
+    // `@every` jobs are due right at start, the replicas sort out who runs them
+    fn first(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Schedule::Every(_) => now,
+            Schedule::Cron(_) => self.next_after(now),
+        }
+    }
+
+    fn next_after(&self, t: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Schedule::Every(d) => t + *d,
+            // checked while parsing
+            Schedule::Cron(c) => c.next_after(t).unwrap(),
+        }
+    }
+
+    // a run started after this has already taken the turn due at `due`, here or on another replica
+    fn taken_since(&self, due: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Schedule::Every(d) => due - *d * 9 / 10,
+            Schedule::Cron(_) => due - TimeDelta::seconds(30),
+        }
+    }
+}
+
+/// Periodic work, registered in the `Scheduler`.
+#[async_trait]
+pub trait Job: Send + Sync {
+    /// Names the lock and the status row, must not change between releases.
+    fn name(&self) -> &'static str;
+    /// Does the work, the returned line is shown to admins.
+    async fn run(&self, db_client: &DbClient<'_>) -> Result<String, tokio_postgres::Error>;
+}
+
+/// Runs jobs on their schedules. Every replica runs a scheduler, an advisory lock
+/// and the start time of the last run decide which one does a turn.
+pub struct Scheduler {
+    pool: &'static PoolWrapper,
+    jobs: Vec<(Schedule, Arc<dyn Job>)>,
+}
+
+impl Scheduler {
+    pub fn new(pool: &'static PoolWrapper) -> Self {
+        Scheduler { pool, jobs: vec![] }
+    }
+
+    pub fn register(&mut self, schedule: Schedule, job: impl Job + 'static) {
+        self.jobs.push((schedule, Arc::new(job)));
+    }
+
+    /// Runs the jobs until `shutdown` is set, runs in progress are let finish.
+    pub fn start(self, shutdown: watch::Receiver<bool>) -> JoinHandle<()> {
+        let pool = self.pool;
+        tokio::spawn(async move {
+            let mut running = JoinSet::new();
+            for (schedule, job) in self.jobs {
+                running.spawn(run_loop(pool, schedule, job, shutdown.clone()));
+            }
+            while running.join_next().await.is_some() {}
+        })
+    }
+}
+
+async fn run_loop(
+    pool: &'static PoolWrapper,
+    schedule: Schedule,
+    job: Arc<dyn Job>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let name = job.name();
+    if let Ok(db_client) = pool.try_get().await.log_err() {
+        let _ = job::register(&db_client, name, &schedule.to_string())
+            .await
+            .log_err();
+    }
+    let mut due = schedule.first(Utc::now());
+    while !*shutdown.borrow() {
+        let wait = (due - Utc::now()).to_std().unwrap_or_default();
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => (),
+            _ = shutdown.changed() => return,
+        }
+        // a panicking job only loses its turn
+        let run = run_once(pool, schedule.clone(), job.clone(), due)
+            .instrument(tracing::info_span!("job", name));
+        if let Err(e) = tokio::spawn(run).await {
+            tracing::error!(job = name, error = %e, "job panicked");
+            if let Ok(db_client) = pool.try_get().await.log_err() {
+                let _ = job::finish(&db_client, name, false, "panicked")
+                    .await
+                    .log_err();
+            }
+        }
+        due = schedule.next_after(due.max(Utc::now()));
+    }
+}
+
+async fn run_once(
+    pool: &'static PoolWrapper,
+    schedule: Schedule,
+    job: Arc<dyn Job>,
+    due: DateTime<Utc>,
+) {
+    let name = job.name();
+    let Ok(db_client) = pool.try_get().await.log_err() else {
+        return;
+    };
+    // a session lock, so the job runs on the same connection outside of a transaction
+    let locked = db_client
+        .query_one(
+            "SELECT pg_try_advisory_lock($1, hashtext($2))",
+            &[&LOCK_SPACE, &name],
+        )
+        .await
+        .log_err()
+        .is_ok_and(|row| row.get::<_, bool>(0));
+    if !locked {
+        tracing::debug!("running on another replica");
+        return;
+    }
+
+    // the connection goes back to the pool, it must not keep the lock even if the job panics
+    let run = AssertUnwindSafe(run_locked(&db_client, &schedule, &*job, due))
+        .catch_unwind()
+        .await;
+    let _ = db_client
+        .execute(
+            "SELECT pg_advisory_unlock($1, hashtext($2))",
+            &[&LOCK_SPACE, &name],
+        )
+        .await
+        .log_err();
+    if let Err(panic) = run {
+        panic::resume_unwind(panic);
+    }
+}
+
+async fn run_locked(
+    db_client: &DbClient<'_>,
+    schedule: &Schedule,
+    job: &dyn Job,
+    due: DateTime<Utc>,
+) {
+    let name = job.name();
+    match job::get_started_at(db_client, name).await.log_err() {
+        Ok(Some(started_at)) if started_at >= schedule.taken_since(due) => {
+            tracing::debug!(%started_at, "already done");
+            return;
+        }
+        Ok(_) => (),
+        Err(_) => return,
+    }
+    let _ = job::start(db_client, name).await.log_err();
+    let (succeeded, report) = match job.run(db_client).await {
+        Ok(report) => {
+            tracing::info!(report, "job done");
+            (true, report)
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "job failed");
+            (false, e.to_string())
+        }
+    };
+    let _ = job::finish(db_client, name, succeeded, &report)
+        .await
+        .log_err();
+}
+
+/// Flags overdue quests and, after the grace period, returns them to the board.
+pub struct OverdueCheck {
+    pub rules: Rules,
+}
+
+#[async_trait]
+impl Job for OverdueCheck {
+    fn name(&self) -> &'static str {
+        "overdue_check"
+    }
+
+    async fn run(&self, db_client: &DbClient<'_>) -> Result<String, tokio_postgres::Error> {
+        let flagged = task::flag_overdue(db_client).await?;
+        let returned = match self.rules.overdue_grace_hours {
+            Some(grace_hours) => task::return_overdue(db_client, grace_hours).await?,
+            None => 0,
+        };
+
+        Ok(format!(
+            "просрочено заданий: {flagged}, возвращено на доску: {returned}"
+        ))
+    }
+}
+
+/// Removes expired sessions.
+pub struct SessionCleanup;
+
+#[async_trait]
+impl Job for SessionCleanup {
+    fn name(&self) -> &'static str {
+        "session_cleanup"
+    }
+
+    async fn run(&self, db_client: &DbClient<'_>) -> Result<String, tokio_postgres::Error> {
+        let removed = session::delete_expired(db_client).await?;
+
+        Ok(format!("удалено истекших сессий: {removed}"))
+    }
+}
+
+/// Stores the standings of the day for every leaderboard metric.
+pub struct LeaderboardSnapshot;
+
+#[async_trait]
+impl Job for LeaderboardSnapshot {
+    fn name(&self) -> &'static str {
+        "leaderboard_snapshot"
+    }
+
+    async fn run(&self, db_client: &DbClient<'_>) -> Result<String, tokio_postgres::Error> {
+        let mut stored = 0;
+        for metric in Metric::ALL {
+            stored += leaderboard::snapshot(db_client, metric).await?;
+        }
+
+        Ok(format!("сохранено мест в рейтингах: {stored}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    fn next(expr: &str, after: &str) -> DateTime<Utc> {
+        expr.parse::<Cron>().unwrap().next_after(at(after)).unwrap()
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(5 * 60)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(2 * 60 * 60)));
+        assert_eq!(parse_duration("1d"), Ok(Duration::from_secs(24 * 60 * 60)));
+        for bad in ["", "m", "0m", "5", "5x", "-5m", "1.5h", "5ч"] {
+            assert!(parse_duration(bad).is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn every_round_trips() {
+        for (input, shown) in [
+            ("@every 90m", "@every 90m"),
+            ("@every 120m", "@every 2h"),
+            ("@every 48h", "@every 2d"),
+            ("@every 45s", "@every 45s"),
+        ] {
+            assert_eq!(input.parse::<Schedule>().unwrap().to_string(), shown);
+        }
+    }
+
+    #[test]
+    fn steps() {
+        let cron: Cron = "*/15 * * * *".parse().unwrap();
+        assert_eq!(cron.minutes, 1 | 1 << 15 | 1 << 30 | 1 << 45);
+        // a start with a step runs to the end of the range
+        let cron: Cron = "5/20 * * * *".parse().unwrap();
+        assert_eq!(cron.minutes, 1 << 5 | 1 << 25 | 1 << 45);
+        let cron: Cron = "0 8-18/5 * * *".parse().unwrap();
+        assert_eq!(cron.hours, 1 << 8 | 1 << 13 | 1 << 18);
+        assert_eq!(
+            next("*/15 * * * *", "2026-10-19T10:15:00Z"),
+            at("2026-10-19T10:30:00Z")
+        );
+    }
+
+    #[test]
+    fn ranges_and_lists() {
+        // weekdays from nine to five, monday 2026-10-19
+        assert_eq!(
+            next("0 9-17 * * 1-5", "2026-10-19T17:30:00Z"),
+            at("2026-10-20T09:00:00Z")
+        );
+        assert_eq!(
+            next("0 9-17 * * 1-5", "2026-10-23T17:00:00Z"),
+            at("2026-10-26T09:00:00Z")
+        );
+        assert_eq!(
+            next("10,40 6 * * *", "2026-10-19T06:10:00Z"),
+            at("2026-10-19T06:40:00Z")
+        );
+        assert_eq!(
+            next("0 0 1 1,7 *", "2026-10-19T00:00:00Z"),
+            at("2027-01-01T00:00:00Z")
+        );
+    }
+
+    #[test]
+    fn day_of_month_or_day_of_week() {
+        // either the 13th or a friday, as in crontab
+        assert_eq!(
+            next("0 0 13 * 5", "2026-10-19T00:00:00Z"),
+            at("2026-10-23T00:00:00Z")
+        );
+        assert_eq!(
+            next("0 0 13 * 5", "2026-12-12T00:00:00Z"),
+            at("2026-12-13T00:00:00Z")
+        );
+        // a restricted day of week alone leaves the days of month out
+        assert_eq!(
+            next("0 0 * * 5", "2026-12-12T00:00:00Z"),
+            at("2026-12-18T00:00:00Z")
+        );
+    }
+
+    #[test]
+    fn seven_is_sunday() {
+        let sunday: Cron = "0 0 * * 0".parse().unwrap();
+        let seven: Cron = "0 0 * * 7".parse().unwrap();
+        assert_eq!(sunday.weekdays, seven.weekdays);
+        assert_eq!(
+            next("0 0 * * 7", "2026-10-19T00:00:00Z"),
+            at("2026-10-25T00:00:00Z")
+        );
+        assert_eq!(
+            next("0 0 * * 5-7", "2026-10-24T12:00:00Z"),
+            at("2026-10-25T00:00:00Z")
+        );
+    }
+
+    #[test]
+    fn never_comes() {
+        for expr in ["0 0 30 2 *", "0 0 31 4,6,9,11 *"] {
+            let err = expr.parse::<Cron>().unwrap_err();
+            assert!(err.contains("never comes"), "{expr}: {err}");
+        }
+        // the 31st of april never comes, but fridays do
+        assert!("0 0 31 4 5".parse::<Cron>().is_ok());
+    }
+
+    #[test]
+    fn invalid_fields() {
+        for expr in [
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "10-5 * * * *",
+            "a * * * *",
+        ] {
+            assert!(expr.parse::<Cron>().is_err(), "{expr}");
+        }
+    }
+}
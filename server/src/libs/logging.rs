@@ -0,0 +1,68 @@
+use std::{
+    fmt::Display,
+    io::{self, IsTerminal},
+    panic::Location,
+};
+
+use axum::{extract::Request, http::HeaderValue};
+use tower_http::request_id::{MakeRequestId, RequestId};
+use tracing::Span;
+use tracing_subscriber::EnvFilter;
+
+use super::config::{LogFormat, Logging};
+
+/// Installs the global subscriber.
+pub fn init(config: &Logging) {
+    // the filter is checked while the config is validated
+    let filter = EnvFilter::new(&config.filter);
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_ansi(io::stdout().is_terminal());
+    match config.format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+}
+
+/// Request ids for `x-request-id`, unless the proxy in front has set one.
+#[derive(Clone, Copy)]
+pub struct MakeRequestNanoid;
+
+impl MakeRequestId for MakeRequestNanoid {
+    fn make_request_id<B>(&mut self, _: &axum::http::Request<B>) -> Option<RequestId> {
+        HeaderValue::from_str(&nanoid::nanoid!())
+            .ok()
+            .map(RequestId::new)
+    }
+}
+
+// one span per request, everything logged while handling it carries the id
+pub fn make_span(req: &Request) -> Span {
+    let request_id = req
+        .headers()
+        .get("x-request-id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+
+    tracing::info_span!(
+        "request",
+        method = %req.method(),
+        uri = %req.uri(),
+        request_id,
+    )
+}
+
+/// Logs the error of a result that is handled without it, where it happened is kept.
+pub trait LogErr {
+    fn log_err(self) -> Self;
+}
+
+impl<T, E: Display> LogErr for Result<T, E> {
+    #[track_caller]
+    fn log_err(self) -> Self {
+        if let Err(e) = &self {
+            tracing::error!(error = %e, location = %Location::caller(), "handled an error");
+        }
+        self
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag, TagEnd};
+use tera::{to_value, try_get_value, Value};
+
+const SAFE_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
+fn is_login_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '.'
+}
+
+/// Logins mentioned in the text as `@login`.
+pub fn mentions(text: &str) -> Vec<&str> {
+    let mut found = vec![];
+    let mut rest = text;
+
+    while let Some(i) = rest.find('@') {
+        // skip emails, a mention starts a word
+        let starts_word = text[..text.len() - rest.len() + i]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !is_login_char(c));
+        rest = &rest[i + 1..];
+        let end = rest.find(|c| !is_login_char(c)).unwrap_or(rest.len());
+        let login = rest[..end].trim_end_matches('.');
+        if starts_word && !login.is_empty() && !found.contains(&login) {
+            found.push(login);
+        }
+        rest = &rest[end..];
+    }
+
+    found
+}
+
+fn is_safe_url(url: &str) -> bool {
+    match url.split_once(':') {
+        // relative links have no scheme, a colon after a slash belongs to the path
+        Some((scheme, _)) if !scheme.contains('/') => {
+            SAFE_SCHEMES.contains(&scheme.to_lowercase().as_str())
+        }
+        _ => true,
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut s = String::with_capacity(text.len());
+    html::push_html(&mut s, [Event::Text(text.into())].into_iter());
+    s
+}
+
+// splits text so known logins become highlighted mentions
+fn highlight_mentions<'a>(text: CowStr<'a>, known: &[String]) -> Vec<Event<'a>> {
+    let mut events = vec![];
+    let mut rest: &str = &text;
+    let mut pending = String::new();
+
+    while let Some(i) = rest.find('@') {
+        pending.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        let end = rest.find(|c| !is_login_char(c)).unwrap_or(rest.len());
+        let login = rest[..end].trim_end_matches('.');
+        let starts_word = pending
+            .chars()
+            .next_back()
+            .is_none_or(|c| !is_login_char(c));
+        if starts_word && known.iter().any(|k| k.as_str() == login) {
+            if !pending.is_empty() {
+                events.push(Event::Text(std::mem::take(&mut pending).into()));
+            }
+            events.push(Event::InlineHtml(
+                format!("<mark>@{}</mark>", escape(login)).into(),
+            ));
+            rest = &rest[login.len()..];
+        } else {
+            pending.push('@');
+        }
+    }
+    pending.push_str(rest);
+    if !pending.is_empty() {
+        events.push(Event::Text(pending.into()));
+    }
+
+    events
+}
+
+/// Renders user markdown to html. Raw html is escaped and links
+/// with scripting schemes are dropped, so the result is safe to embed.
+pub fn render(text: &str, known_mentions: &[String]) -> String {
+    let mut in_code = false;
+    let parser = Parser::new_ext(text, Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES)
+        .flat_map(|event| match event {
+            Event::Start(Tag::CodeBlock(_)) => {
+                in_code = true;
+                vec![event]
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                in_code = false;
+                vec![event]
+            }
+            Event::Html(s) | Event::InlineHtml(s) => vec![Event::Text(s)],
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => vec![Event::Start(Tag::Link {
+                link_type,
+                dest_url: if is_safe_url(&dest_url) {
+                    dest_url
+                } else {
+                    "".into()
+                },
+                title,
+                id,
+            })],
+            Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => vec![Event::Start(Tag::Image {
+                link_type,
+                dest_url: if is_safe_url(&dest_url) {
+                    dest_url
+                } else {
+                    "".into()
+                },
+                title,
+                id,
+            })],
+            Event::Text(s) if !in_code => highlight_mentions(s, known_mentions),
+            event => vec![event],
+        });
+
+    let mut out = String::new();
+    html::push_html(&mut out, parser);
+    out
+}
+
+/// Tera filter: `{{ text | markdown(mentions=logins) | safe }}`
+pub fn filter(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let text = try_get_value!("markdown", "value", String, value);
+    let known = match args.get("mentions") {
+        Some(v) => try_get_value!("markdown", "mentions", Vec<String>, v),
+        None => vec![],
+    };
+
+    Ok(to_value(render(&text, &known))?)
+}
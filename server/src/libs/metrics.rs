@@ -0,0 +1,307 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+
+use super::{ai::Stats, db::PoolWrapper};
+
+// upper bounds of the latency histogram, in seconds
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Clone, Copy)]
+pub enum TaskEvent {
+    Created,
+    Claimed,
+    Completed,
+}
+
+impl TaskEvent {
+    const ALL: [TaskEvent; 3] = [TaskEvent::Created, TaskEvent::Claimed, TaskEvent::Completed];
+
+    fn name(&self) -> &'static str {
+        match self {
+            TaskEvent::Created => "dungeon_tasks_created_total",
+            TaskEvent::Claimed => "dungeon_tasks_claimed_total",
+            TaskEvent::Completed => "dungeon_tasks_completed_total",
+        }
+    }
+
+    fn help(&self) -> &'static str {
+        match self {
+            TaskEvent::Created => "Quests put on the board.",
+            TaskEvent::Claimed => "Quests taken by adventurers.",
+            TaskEvent::Completed => "Quests completed.",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+// method and route pattern, and the status code for counts
+type RouteKey = (Box<str>, Box<str>);
+type StatusKey = (Box<str>, Box<str>, u16);
+
+/// In-process registry rendered in the Prometheus text format by `/metrics`.
+#[derive(Default)]
+pub struct Metrics {
+    requests: Mutex<HashMap<StatusKey, u64>>,
+    latency: Mutex<HashMap<RouteKey, Histogram>>,
+    events: [AtomicU64; TaskEvent::ALL.len()],
+}
+
+// a metric without labels
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, value: f64) {
+    let _ = writeln!(
+        out,
+        "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}"
+    );
+}
+
+// label values are route patterns and methods, quotes and backslashes are all that needs escaping
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl Metrics {
+    pub fn record(&self, event: TaskEvent) {
+        self.events[event as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn observe(&self, method: &str, route: &str, status: u16, seconds: f64) {
+        *self
+            .requests
+            .lock()
+            .unwrap()
+            .entry((method.into(), route.into(), status))
+            .or_default() += 1;
+        self.latency
+            .lock()
+            .unwrap()
+            .entry((method.into(), route.into()))
+            .or_default()
+            .observe(seconds);
+    }
+
+    pub fn render(&self, pool: &PoolWrapper, recommender: Option<Stats>) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP dungeon_http_requests_total HTTP requests by route and status.\n");
+        out.push_str("# TYPE dungeon_http_requests_total counter\n");
+        let mut requests: Vec<_> = self
+            .requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(k, v)| (k.clone(), *v))
+            .collect();
+        requests.sort();
+        for ((method, route, status), count) in requests {
+            let _ = writeln!(
+                out,
+                "dungeon_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{status}\"}} {count}",
+                escape(&method),
+                escape(&route),
+            );
+        }
+
+        out.push_str(
+            "# HELP dungeon_http_request_duration_seconds HTTP request latency by route.\n",
+        );
+        out.push_str("# TYPE dungeon_http_request_duration_seconds histogram\n");
+        {
+            let latency = self.latency.lock().unwrap();
+            let mut routes: Vec<_> = latency.keys().collect();
+            routes.sort();
+            for key in routes {
+                let h = &latency[key];
+                let labels = format!("method=\"{}\",route=\"{}\"", escape(&key.0), escape(&key.1));
+                for (bound, count) in BUCKETS.iter().zip(h.buckets) {
+                    let _ = writeln!(
+                        out,
+                        "dungeon_http_request_duration_seconds_bucket{{{labels},le=\"{bound}\"}} {count}"
+                    );
+                }
+                let _ = writeln!(
+                    out,
+                    "dungeon_http_request_duration_seconds_bucket{{{labels},le=\"+Inf\"}} {}",
+                    h.count
+                );
+                let _ = writeln!(
+                    out,
+                    "dungeon_http_request_duration_seconds_sum{{{labels}}} {}",
+                    h.sum
+                );
+                let _ = writeln!(
+                    out,
+                    "dungeon_http_request_duration_seconds_count{{{labels}}} {}",
+                    h.count
+                );
+            }
+        }
+
+        let state = pool.state();
+        let stats = &state.statistics;
+        let pool_metrics = [
+            (
+                "dungeon_db_pool_connections",
+                "gauge",
+                "Open connections.",
+                state.connections as f64,
+            ),
+            (
+                "dungeon_db_pool_idle_connections",
+                "gauge",
+                "Idle connections.",
+                state.idle_connections as f64,
+            ),
+            (
+                "dungeon_db_pool_gets_direct_total",
+                "counter",
+                "Connections handed out without waiting.",
+                stats.get_direct as f64,
+            ),
+            (
+                "dungeon_db_pool_gets_waited_total",
+                "counter",
+                "Connections handed out after waiting.",
+                stats.get_waited as f64,
+            ),
+            (
+                "dungeon_db_pool_gets_timed_out_total",
+                "counter",
+                "Waits for a connection that timed out.",
+                stats.get_timed_out as f64,
+            ),
+            (
+                "dungeon_db_pool_wait_seconds_total",
+                "counter",
+                "Time spent waiting for a connection.",
+                stats.get_wait_time.as_secs_f64(),
+            ),
+            (
+                "dungeon_db_pool_connections_created_total",
+                "counter",
+                "Connections opened.",
+                stats.connections_created as f64,
+            ),
+        ];
+        for (name, kind, help, value) in pool_metrics {
+            write_metric(&mut out, name, kind, help, value);
+        }
+
+        if let Some(s) = recommender {
+            let recommender_metrics = [
+                (
+                    "dungeon_recommender_calls_total",
+                    "counter",
+                    "Recommendation requests.",
+                    s.calls as f64,
+                ),
+                (
+                    "dungeon_recommender_cache_hits_total",
+                    "counter",
+                    "Requests answered from the cache.",
+                    s.cache_hits as f64,
+                ),
+                (
+                    "dungeon_recommender_failures_total",
+                    "counter",
+                    "Failed calls to the recommender.",
+                    s.failures as f64,
+                ),
+                (
+                    "dungeon_recommender_timeouts_total",
+                    "counter",
+                    "Calls to the recommender that timed out.",
+                    s.timeouts as f64,
+                ),
+                (
+                    "dungeon_recommender_short_circuits_total",
+                    "counter",
+                    "Requests skipped while the circuit was open.",
+                    s.short_circuits as f64,
+                ),
+                (
+                    "dungeon_recommender_latency_seconds_total",
+                    "counter",
+                    "Time spent in recommender calls.",
+                    s.latency_ms as f64 / 1000.0,
+                ),
+                (
+                    "dungeon_recommender_circuit_open",
+                    "gauge",
+                    "Whether calls to the recommender are paused.",
+                    s.circuit_open as u8 as f64,
+                ),
+            ];
+            for (name, kind, help, value) in recommender_metrics {
+                write_metric(&mut out, name, kind, help, value);
+            }
+        }
+
+        for event in TaskEvent::ALL {
+            let value = self.events[event as usize].load(Ordering::Relaxed);
+            write_metric(
+                &mut out,
+                event.name(),
+                "counter",
+                event.help(),
+                value as f64,
+            );
+        }
+
+        out
+    }
+}
+
+/// Middleware counting requests and their latency by matched route.
+pub async fn track(
+    State(metrics): State<&'static Metrics>,
+    matched: Option<MatchedPath>,
+    req: Request,
+    next: Next,
+) -> Response {
+    // unmatched paths would let anyone grow the label set
+    let route = matched.map_or("unmatched".to_owned(), |m| m.as_str().to_owned());
+    let method = req.method().to_string();
+    let started = Instant::now();
+    let response = next.run(req).await;
+    metrics.observe(
+        &method,
+        &route,
+        response.status().as_u16(),
+        started.elapsed().as_secs_f64(),
+    );
+
+    response
+}
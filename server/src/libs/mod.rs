@@ -1,3 +1,13 @@
 pub mod ai;
+pub mod audit;
 pub mod auth;
+pub mod charts;
+pub mod config;
+pub mod csv;
 pub mod db;
+pub mod jobs;
+pub mod logging;
+pub mod markdown;
+pub mod metrics;
+pub mod sessions;
+pub mod storage;
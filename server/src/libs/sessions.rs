@@ -0,0 +1,99 @@
+use std::fmt;
+
+use async_trait::async_trait;
+use axum_login::tower_sessions::{
+    cookie::time::OffsetDateTime,
+    session::{Id, Record},
+    session_store::{self, Error},
+    SessionStore,
+};
+use chrono::{DateTime, Utc};
+
+use super::db::PoolWrapper;
+use crate::entities::session;
+
+/// Sessions in Postgres, so they survive restarts and every replica sees them.
+/// Expired ones are removed by the `session_cleanup` job.
+#[derive(Clone)]
+pub struct PgStore {
+    pool: &'static PoolWrapper,
+}
+
+impl PgStore {
+    pub fn new(pool: &'static PoolWrapper) -> Self {
+        PgStore { pool }
+    }
+}
+
+impl fmt::Debug for PgStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PgStore")
+    }
+}
+
+fn backend(e: impl fmt::Display) -> Error {
+    Error::Backend(e.to_string())
+}
+
+fn to_chrono(t: OffsetDateTime) -> DateTime<Utc> {
+    DateTime::from_timestamp(t.unix_timestamp(), t.nanosecond()).unwrap_or_default()
+}
+
+fn encode(record: &Record) -> session_store::Result<(String, String, DateTime<Utc>)> {
+    let data = serde_json::to_string(&record.data).map_err(|e| Error::Encode(e.to_string()))?;
+
+    Ok((record.id.to_string(), data, to_chrono(record.expiry_date)))
+}
+
+#[async_trait]
+impl SessionStore for PgStore {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        let db_client = self.pool.try_get().await.map_err(backend)?;
+        loop {
+            let (id, data, expires_at) = encode(record)?;
+            if session::create(&db_client, &id, &data, expires_at)
+                .await
+                .map_err(backend)?
+            {
+                return Ok(());
+            }
+            record.id = Id::default();
+        }
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        let (id, data, expires_at) = encode(record)?;
+        let db_client = self.pool.try_get().await.map_err(backend)?;
+        session::save(&db_client, &id, &data, expires_at)
+            .await
+            .map_err(backend)?;
+
+        Ok(())
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        let db_client = self.pool.try_get().await.map_err(backend)?;
+        let Some((data, expires_at)) = session::load(&db_client, &session_id.to_string())
+            .await
+            .map_err(backend)?
+        else {
+            return Ok(None);
+        };
+
+        Ok(Some(Record {
+            id: *session_id,
+            data: serde_json::from_str(&data).map_err(|e| Error::Decode(e.to_string()))?,
+            expiry_date: OffsetDateTime::from_unix_timestamp(expires_at.timestamp())
+                .map_err(|e| Error::Decode(e.to_string()))?,
+        }))
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        let db_client = self.pool.try_get().await.map_err(backend)?;
+        session::delete(&db_client, &session_id.to_string())
+            .await
+            .map_err(backend)?;
+
+        Ok(())
+    }
+}
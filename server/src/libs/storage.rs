@@ -0,0 +1,52 @@
+use std::{io, path::PathBuf};
+
+use async_trait::async_trait;
+use tokio::fs;
+
+/// Blob storage for uploaded files. Keys are generated by the server,
+/// so backends may use them as file names or object keys as is.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn put(&self, key: &str, data: &[u8]) -> io::Result<()>;
+    async fn get(&self, key: &str) -> io::Result<Vec<u8>>;
+    async fn delete(&self, key: &str) -> io::Result<()>;
+}
+
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub async fn new(root: impl Into<PathBuf>) -> io::Result<LocalStorage> {
+        let root = root.into();
+        fs::create_dir_all(&root).await?;
+
+        Ok(LocalStorage { root })
+    }
+
+    fn path(&self, key: &str) -> io::Result<PathBuf> {
+        // keys never leave the storage directory
+        if key.is_empty() || key.contains(['/', '\\']) || key.starts_with('.') {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid key"));
+        }
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        fs::write(self.path(key)?, data).await
+    }
+
+    async fn get(&self, key: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.path(key)?).await
+    }
+
+    async fn delete(&self, key: &str) -> io::Result<()> {
+        match fs::remove_file(self.path(key)?).await {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            r => r,
+        }
+    }
+}
@@ -1,69 +1,181 @@
 use api::api;
-use axum::Router;
+use axum::{middleware, Router};
 use axum_login::{
-    tower_sessions::{MemoryStore, SessionManagerLayer},
+    tower_sessions::{cookie::time, Expiry, SessionManagerLayer},
     AuthManagerLayerBuilder,
 };
-use lazy_static::lazy_static;
 use libs::{
+    ai::{HttpRecommender, NativeRecommender, Recommender, ResilientRecommender},
     auth::Backend,
+    config::{Config, RecommenderKind},
     db::{init_db, PoolWrapper},
+    jobs::{LeaderboardSnapshot, OverdueCheck, Scheduler, SessionCleanup},
+    logging::{self, MakeRequestNanoid},
+    markdown,
+    metrics::{self, Metrics},
+    sessions::PgStore,
+    storage::{LocalStorage, Storage},
 };
-use std::env;
+use std::{collections::HashMap, net::SocketAddr, process, time::Duration};
 use tera::Tera;
+use tokio::{signal, sync::watch};
+use tower::ServiceBuilder;
+use tower_http::{
+    request_id::{PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::{DefaultOnResponse, TraceLayer},
+    LatencyUnit,
+};
+use tracing::Level;
 
 mod api;
 mod entities;
 mod libs;
 
-// env
-
-lazy_static! {
-    pub static ref STATIC_PATH: &'static str = {
-        let s = &env::var("STATIC_PATH").unwrap_or("static".to_owned());
-        let s: &'static str = s.clone().leak();
-
-        s
-    };
-    pub static ref AI_HOST: &'static str = {
-        let s = &env::var("AI_HOST").unwrap_or("http://localhost:8080".to_owned());
-        let s: &'static str = s.clone().leak();
-
-        s
-    };
-}
-
 // app state
 
 #[derive(Clone)]
 struct AppState {
-    http_client: reqwest::Client,
+    config: &'static Config,
+    recommender: &'static dyn Recommender,
     pool: &'static PoolWrapper,
     template: &'static Tera,
+    storage: &'static dyn Storage,
+    metrics: &'static Metrics,
 }
 
 #[tokio::main]
 async fn main() {
+    // config
+    let config: &'static Config = match Config::load() {
+        Ok(c) => Box::leak(Box::new(c)),
+        Err(e) => {
+            // there is no logger without a config
+            eprintln!("invalid config: {e}");
+            process::exit(1);
+        }
+    };
+    // logging
+    logging::init(&config.logging);
     // db
-    let pool = init_db().await;
+    let pool = init_db(&config.db).await;
+    // background jobs
+    let jobs = &config.jobs;
+    let mut scheduler = Scheduler::new(pool);
+    scheduler.register(
+        jobs.overdue_check.clone(),
+        OverdueCheck {
+            rules: config.rules,
+        },
+    );
+    scheduler.register(jobs.session_cleanup.clone(), SessionCleanup);
+    scheduler.register(jobs.leaderboard_snapshot.clone(), LeaderboardSnapshot);
+    let (stop_jobs, jobs_stopped) = watch::channel(false);
+    let scheduler = scheduler.start(jobs_stopped);
     // templates
-    let tera = Tera::new(&format!("{}/templates/**/*", *STATIC_PATH)).unwrap();
+    let mut tera = Tera::new(&format!("{}/templates/**/*", config.static_path.display())).unwrap();
+    tera.register_filter("markdown", markdown::filter);
+    let features = config.features;
+    tera.register_function("feature", move |args: &HashMap<String, tera::Value>| {
+        Ok(tera::Value::Bool(
+            match args.get("name").and_then(|v| v.as_str()) {
+                Some("attachments") => features.attachments,
+                Some("comments") => features.comments,
+                Some("recommendations") => features.recommendations,
+                _ => return Err("feature: unknown name".into()),
+            },
+        ))
+    });
+    // file storage
+    let storage = LocalStorage::new(&config.storage.path)
+        .await
+        .expect("failed to create storage directory");
+    // recommender
+    let rc = &config.recommender;
+    let timeout = Duration::from_millis(rc.timeout_ms);
+    let recommender: &'static dyn Recommender = match rc.kind {
+        RecommenderKind::Native => Box::leak(Box::new(ResilientRecommender::new(
+            NativeRecommender::new(rc.top_n),
+            timeout,
+        ))),
+        RecommenderKind::Http => Box::leak(Box::new(ResilientRecommender::new(
+            HttpRecommender::new(reqwest::Client::new(), &rc.host, rc.top_n),
+            timeout,
+        ))),
+    };
     // app state
+    let metrics: &'static Metrics = Box::leak(Box::default());
     let state = AppState {
+        metrics,
+        config,
         pool,
         template: Box::leak(Box::new(tera)),
-        http_client: reqwest::Client::new(),
+        storage: Box::leak(Box::new(storage)),
+        recommender,
     };
     // Session layer.
-    let session_store = MemoryStore::default();
-    let session_layer = SessionManagerLayer::new(session_store);
+    let session_store = PgStore::new(pool);
+    let session_layer = SessionManagerLayer::new(session_store)
+        .with_name(config.sessions.cookie_name.as_str())
+        .with_secure(config.sessions.secure)
+        .with_expiry(match config.sessions.inactivity_hours {
+            Some(h) => Expiry::OnInactivity(time::Duration::hours(h.into())),
+            None => Expiry::OnSessionEnd,
+        });
     // Auth service.
-    let backend = Backend::new(&pool);
+    let backend = Backend::new(pool);
     let auth_layer = AuthManagerLayerBuilder::new(backend, session_layer).build();
     // launch server
     let app = Router::new()
         .nest("/", api(state.clone()).with_state(state))
-        .layer(auth_layer);
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+        .layer(auth_layer)
+        .layer(middleware::from_fn_with_state(metrics, metrics::track))
+        .layer(
+            ServiceBuilder::new()
+                .layer(SetRequestIdLayer::x_request_id(MakeRequestNanoid))
+                .layer(
+                    TraceLayer::new_for_http()
+                        .make_span_with(logging::make_span)
+                        .on_response(
+                            DefaultOnResponse::new()
+                                .level(Level::INFO)
+                                .latency_unit(LatencyUnit::Millis),
+                        ),
+                )
+                .layer(PropagateRequestIdLayer::x_request_id()),
+        );
+    let listener = tokio::net::TcpListener::bind(config.listen).await.unwrap();
+    tracing::info!(addr = %config.listen, "listening");
+    // the peer address goes to the audit log
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .unwrap();
+    // requests are drained, jobs in progress are let finish
+    let _ = stop_jobs.send(true);
+    let _ = scheduler.await;
+    tracing::info!("stopped");
+}
+
+async fn shutdown_signal() {
+    let interrupt = async {
+        signal::ctrl_c().await.expect("failed to listen for SIGINT");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => (),
+        _ = terminate => (),
+    }
+    tracing::info!("shutting down");
 }
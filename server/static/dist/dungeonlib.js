@@ -38,6 +38,13 @@ async function inviteUser() {
   setClipboard(b.token);
 }
 
+// the export takes the same filters as the list
+function exportAudit(format) {
+  const params = new URLSearchParams(new FormData(document.getElementById("audit-filter")));
+  params.set("format", format);
+  window.location.href = "/api/audit/export?" + params;
+}
+
 function nextLine(callback) {
   const lines = document.getElementById("dialog-text").children;
   let found = false;